@@ -0,0 +1,253 @@
+//! Interactive QUEST+ session with a normal CDF psychometric function.
+//!
+//! Usage: `questplus <config.json> <session.json>`
+//!
+//! The session is resumed from `<session.json>` if it exists, otherwise started from
+//! `<config.json>`, and is saved back to `<session.json>` on exit. A config looks like
+//!
+//! ```json
+//! {
+//!     "stim_domain": {"intensity": [0, 1, 2, 3, 4, 5]},
+//!     "param_domain": {
+//!         "mean": [1, 2, 3, 4],
+//!         "sd": [0.5, 1, 2],
+//!         "lower_asymptote": [0.5],
+//!         "lapse_rate": [0.01, 0.05]
+//!     },
+//!     "priors": {"lapse_rate": [0.8, 0.2]},
+//!     "stop_rules": [{"MaxTrials": 40}]
+//! }
+//! ```
+//!
+//! where `priors`, `stim_scale`, `stim_selection_method`, `param_estimation_method` and
+//! `stop_rules` are optional.
+use ndarray::prelude::*;
+use questplus::domain::{ParamDomain, ParamPDF, PriorPDFFactory, StimDomain};
+use questplus::error::QuestPlusError;
+use questplus::pf::{NormCDF, NormCDFFunction, NormCDFParamDomain, Outcome, PsychometricFunction};
+use questplus::{ParamEstimationMethod, StimScale, StimSelectionMethod, StopRule};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::{env, fs, process};
+
+#[derive(Debug, serde::Deserialize)]
+struct Config {
+    stim_domain: HashMap<String, Vec<f64>>,
+    param_domain: HashMap<String, Vec<f64>>,
+    #[serde(default)]
+    priors: HashMap<String, Vec<f64>>,
+    stim_scale: Option<StimScale>,
+    stim_selection_method: Option<StimSelectionMethod>,
+    param_estimation_method: Option<ParamEstimationMethod>,
+    #[serde(default)]
+    stop_rules: Vec<StopRule>,
+}
+
+impl Config {
+    fn build(self) -> Result<NormCDF, QuestPlusError> {
+        let pf = NormCDFFunction::default();
+        let stim_domain = StimDomain::from_map(&pf.stim_names(), to_arrays(self.stim_domain))?;
+        let param_domain = ParamDomain::from_map(&pf.param_names(), to_arrays(self.param_domain))?;
+        let prior_pdf = ParamPDF::from_map(&param_domain, to_arrays(self.priors))?
+            .into_dimensionality::<Ix4>()
+            .map_err(QuestPlusError::NDArrayError)?;
+        let param = |name| param_domain.get(name).unwrap().clone();
+        let param_domain = NormCDFParamDomain::new(
+            param("mean"),
+            param("sd"),
+            param("lower_asymptote"),
+            param("lapse_rate"),
+        );
+
+        let mut builder = NormCDF::builder()
+            .stim_domain(stim_domain)
+            .param_domain(param_domain)
+            .prior_pdf(prior_pdf);
+        if let Some(stim_scale) = self.stim_scale {
+            builder = builder.stim_scale(stim_scale);
+        }
+        if let Some(method) = self.stim_selection_method {
+            builder = builder.stim_selection_method(method);
+        }
+        if let Some(method) = self.param_estimation_method {
+            builder = builder.param_estimation_method(method);
+        }
+        let mut qp = builder.build()?;
+        qp.set_stop_rules(self.stop_rules)?;
+        Ok(qp)
+    }
+}
+
+fn to_arrays(map: HashMap<String, Vec<f64>>) -> HashMap<String, Array1<f64>> {
+    map.into_iter()
+        .map(|(name, values)| (name, Array1::from(values)))
+        .collect()
+}
+
+/// Parses a response, or returns `None` if it is not one of `c`/`correct`/`1` or
+/// `i`/`incorrect`/`0`.
+fn parse_outcome(line: &str) -> Option<Outcome> {
+    match line.trim().to_lowercase().as_str() {
+        "c" | "correct" | "1" => Some(Outcome::Correct),
+        "i" | "incorrect" | "0" => Some(Outcome::Incorrect),
+        _ => None,
+    }
+}
+
+/// Writes the session to a temporary file next to `path` and renames it over `path`, so an
+/// interrupted write never leaves a truncated session behind.
+fn save(qp: &NormCDF, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(qp.to_json()?.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn run(config_path: &Path, session_path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut qp = if session_path.exists() {
+        println!("resuming {}", session_path.display());
+        NormCDF::from_json(&fs::read_to_string(session_path)?)?
+    } else {
+        let config: Config = serde_json::from_str(&fs::read_to_string(config_path)?)?;
+        config.build()?
+    };
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    'trials: while !qp.is_finished() {
+        let stim = qp.next_stim()?;
+        let trial = qp.stim_history.len() + 1;
+        let outcome = loop {
+            print!("trial {}: intensity {} [c/i/q] ", trial, stim[0]);
+            io::stdout().flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => break 'trials,
+            };
+            if line.trim() == "q" {
+                break 'trials;
+            }
+            match parse_outcome(&line) {
+                Some(outcome) => break outcome,
+                None => println!("expected c (correct), i (incorrect) or q (quit)"),
+            }
+        };
+        qp.update(stim, outcome)?;
+        save(&qp, session_path)?;
+
+        let estimates = qp.get_param_estimate_values();
+        let estimates: Vec<String> = qp
+            .param_domain
+            .names()
+            .iter()
+            .zip(estimates)
+            .map(|(name, value)| format!("{}={:.4}", name, value))
+            .collect();
+        println!(
+            "estimate: {}, entropy: {:.4}",
+            estimates.join(", "),
+            qp.entropy_history.last().unwrap()
+        );
+    }
+    if let Some(reason) = &qp.stop_reason {
+        println!("finished: {:?}", reason);
+    }
+
+    save(&qp, session_path)?;
+    println!("saved {}", session_path.display());
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: {} <config.json> <session.json>", args[0]);
+        process::exit(2);
+    }
+    if let Err(e) = run(Path::new(&args[1]), Path::new(&args[2])) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "stim_domain": {"intensity": [0, 1, 2, 3, 4, 5]},
+                "param_domain": {
+                    "mean": [1, 2, 3, 4],
+                    "sd": [0.5, 1, 2],
+                    "lower_asymptote": [0.5],
+                    "lapse_rate": [0.01, 0.05]
+                },
+                "priors": {"lapse_rate": [0.8, 0.2]},
+                "param_estimation_method": "Mean",
+                "stop_rules": [{"MaxTrials": 40}]
+            }"#,
+        )
+        .unwrap();
+        let qp = config.build().unwrap();
+        assert_eq!(qp.posterior_pdf.shape(), &[4, 3, 1, 2]);
+        assert!((qp.prior_pdf[[0, 0, 0, 0]] - 0.8 / 12.).abs() < 1e-12);
+        assert_eq!(qp.stop_rules, vec![StopRule::MaxTrials(40)]);
+
+        let config: Config = serde_json::from_str(
+            r#"{
+                "stim_domain": {"intensity": [0, 1, 2]},
+                "param_domain": {"mean": [1, 2], "sd": [0, 1], "lower_asymptote": [0.5]}
+            }"#,
+        )
+        .unwrap();
+        match config.build() {
+            Err(QuestPlusError::ParameterNotExists(missing, _)) => {
+                assert!(missing.contains("lapse_rate"))
+            }
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_parse_outcome() {
+        assert_eq!(parse_outcome("c\n"), Some(Outcome::Correct));
+        assert_eq!(parse_outcome(" Incorrect "), Some(Outcome::Incorrect));
+        assert_eq!(parse_outcome("x"), None);
+    }
+
+    #[test]
+    fn test_save() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "stim_domain": {"intensity": [0, 1, 2, 3, 4, 5]},
+                "param_domain": {
+                    "mean": [1, 2, 3, 4],
+                    "sd": [0.5, 1],
+                    "lower_asymptote": [0.5],
+                    "lapse_rate": [0.01]
+                }
+            }"#,
+        )
+        .unwrap();
+        let mut qp = config.build().unwrap();
+        qp.update(3., Outcome::Correct).unwrap();
+
+        let path = env::temp_dir().join(format!("questplus-test-{}.json", process::id()));
+        save(&qp, &path).unwrap();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        assert!(!Path::new(&tmp_path).exists());
+        let resumed = NormCDF::from_json(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(resumed.stim_history, qp.stim_history);
+        assert_eq!(resumed.posterior_pdf, qp.posterior_pdf);
+    }
+}
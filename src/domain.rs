@@ -0,0 +1,382 @@
+use crate::error::QuestPlusError;
+use itertools::Itertools;
+use ndarray::prelude::*;
+use statrs::distribution::Continuous;
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::ops::Deref;
+
+/// A point of the stimulus domain, one value per dimension in the order of `StimDomain::dims`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stim(pub Vec<f64>);
+
+impl Deref for Stim {
+    type Target = [f64];
+
+    fn deref(&self) -> &[f64] {
+        &self.0
+    }
+}
+
+impl From<f64> for Stim {
+    fn from(intensity: f64) -> Self {
+        Stim(vec![intensity])
+    }
+}
+
+impl From<Vec<f64>> for Stim {
+    fn from(values: Vec<f64>) -> Self {
+        Stim(values)
+    }
+}
+
+impl From<&[f64]> for Stim {
+    fn from(values: &[f64]) -> Self {
+        Stim(values.to_vec())
+    }
+}
+
+impl<const N: usize> From<[f64; N]> for Stim {
+    fn from(values: [f64; N]) -> Self {
+        Stim(values.to_vec())
+    }
+}
+
+/// Grid of stimuli, one named axis per stimulus dimension.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StimDomain {
+    pub dims: Vec<(String, Array1<f64>)>,
+}
+
+impl StimDomain {
+    /// Stimulus domain with a single `intensity` dimension.
+    pub fn new(intensity: Array1<f64>) -> Self {
+        StimDomain::with_dims(vec![("intensity".to_string(), intensity)])
+    }
+
+    pub fn with_dims(dims: Vec<(String, Array1<f64>)>) -> Self {
+        StimDomain { dims }
+    }
+
+    /// Stimulus domain from a map of dimension name to values, ordered by `names` (usually
+    /// `PsychometricFunction::stim_names`).
+    pub fn from_map<K: Borrow<str> + Eq + Hash>(
+        names: &[&str],
+        dims: HashMap<K, Array1<f64>>,
+    ) -> Result<Self, QuestPlusError> {
+        Ok(StimDomain::with_dims(ordered(names, dims)?))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        names(&self.dims)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Array1<f64>> {
+        get(&self.dims, name)
+    }
+
+    pub fn shape(&self) -> Vec<usize> {
+        shape(&self.dims)
+    }
+
+    /// Number of stimuli in the grid.
+    pub fn len(&self) -> usize {
+        self.shape().iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every stimulus of the grid in row-major order.
+    pub fn points(&self) -> Vec<Stim> {
+        points(&self.dims).into_iter().map(Stim).collect()
+    }
+
+    /// Stimulus at the row-major `index` of the grid.
+    pub fn point(&self, mut index: usize) -> Stim {
+        let mut values = vec![0.; self.dims.len()];
+        for (value, (_, dim)) in values.iter_mut().zip(self.dims.iter()).rev() {
+            *value = dim[index % dim.len()];
+            index /= dim.len();
+        }
+        Stim(values)
+    }
+
+    /// Checks that every dimension is non-empty, finite and strictly increasing.
+    pub fn validate(&self) -> Result<(), QuestPlusError> {
+        validate(&self.dims)
+    }
+
+    /// Row-major index of `stim` in the grid.
+    pub(crate) fn index_of(&self, stim: &Stim) -> Result<usize, QuestPlusError> {
+        if stim.len() != self.dims.len() {
+            return Err(QuestPlusError::StimNotExists(stim.to_vec()));
+        }
+        let mut index = 0;
+        for ((_, values), x) in self.dims.iter().zip(stim.iter()) {
+            match values.iter().position(|v| v == x) {
+                Some(i) => index = index * values.len() + i,
+                None => return Err(QuestPlusError::StimNotExists(stim.to_vec())),
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// Grid of parameter values, one named axis per parameter of a psychometric function.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParamDomain {
+    pub params: Vec<(String, Array1<f64>)>,
+}
+
+impl ParamDomain {
+    pub fn new(params: Vec<(String, Array1<f64>)>) -> Self {
+        ParamDomain { params }
+    }
+
+    /// Parameter domain from a map of parameter name to values, ordered by `names` (usually
+    /// `PsychometricFunction::param_names`).
+    pub fn from_map<K: Borrow<str> + Eq + Hash>(
+        names: &[&str],
+        params: HashMap<K, Array1<f64>>,
+    ) -> Result<Self, QuestPlusError> {
+        Ok(ParamDomain::new(ordered(names, params)?))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        names(&self.params)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Array1<f64>> {
+        get(&self.params, name)
+    }
+
+    pub fn shape(&self) -> Vec<usize> {
+        shape(&self.params)
+    }
+
+    /// Every point of the grid in row-major order.
+    pub fn points(&self) -> Vec<Vec<f64>> {
+        points(&self.params)
+    }
+
+    /// Checks that every parameter is non-empty, finite and strictly increasing.
+    pub fn validate(&self) -> Result<(), QuestPlusError> {
+        validate(&self.params)
+    }
+}
+
+fn names(axes: &[(String, Array1<f64>)]) -> Vec<&str> {
+    axes.iter().map(|(name, _)| name.as_str()).collect()
+}
+
+fn get<'a>(axes: &'a [(String, Array1<f64>)], name: &str) -> Option<&'a Array1<f64>> {
+    axes.iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, values)| values)
+}
+
+fn shape(axes: &[(String, Array1<f64>)]) -> Vec<usize> {
+    axes.iter().map(|(_, values)| values.len()).collect()
+}
+
+/// Checks that every axis is non-empty and finite, which any engine needs to run.
+pub(crate) fn check_finite(axes: &[(String, Array1<f64>)]) -> Result<(), QuestPlusError> {
+    for (name, values) in axes {
+        if values.is_empty() {
+            return Err(QuestPlusError::EmptyDomain(name.clone()));
+        }
+        if values.iter().any(|v| !v.is_finite()) {
+            return Err(QuestPlusError::DomainNotFinite(name.clone()));
+        }
+    }
+    Ok(())
+}
+
+fn validate(axes: &[(String, Array1<f64>)]) -> Result<(), QuestPlusError> {
+    check_finite(axes)?;
+    for (name, values) in axes {
+        if values
+            .iter()
+            .zip(values.iter().skip(1))
+            .any(|(a, b)| a >= b)
+        {
+            return Err(QuestPlusError::DomainNotIncreasing(name.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Names among `got` that are not in `names`.
+fn unknown_names<'a>(names: &[&str], got: impl Iterator<Item = &'a str>) -> HashSet<String> {
+    got.filter(|name| !names.contains(name))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// Values of `map` in the order of `names`, which must be exactly the keys of `map`.
+fn ordered<K: Borrow<str> + Eq + Hash>(
+    names: &[&str],
+    mut map: HashMap<K, Array1<f64>>,
+) -> Result<Vec<(String, Array1<f64>)>, QuestPlusError> {
+    let want: HashSet<String> = names.iter().map(|name| name.to_string()).collect();
+    let unknown = unknown_names(names, map.keys().map(|k| k.borrow()));
+    if !unknown.is_empty() {
+        return Err(QuestPlusError::ParameterNotExists(unknown, want));
+    }
+    let got: Vec<&str> = map.keys().map(|k| k.borrow()).collect();
+    let missing = unknown_names(&got, names.iter().cloned());
+    if !missing.is_empty() {
+        let got = got.iter().map(|name| name.to_string()).collect();
+        return Err(QuestPlusError::ParameterNotExists(missing, got));
+    }
+    Ok(names
+        .iter()
+        .map(|name| (name.to_string(), map.remove(*name).unwrap()))
+        .collect())
+}
+
+fn points(axes: &[(String, Array1<f64>)]) -> Vec<Vec<f64>> {
+    axes.iter()
+        .map(|(_, values)| values.iter().cloned())
+        .multi_cartesian_product()
+        .collect()
+}
+
+/// Joint probability mass over a `ParamDomain`. Convert it with `mapv` to run an engine at
+/// another precision.
+pub type ParamPDF = ArrayD<f64>;
+
+pub trait PriorPDFFactory {
+    fn new(
+        param_domain: &ParamDomain,
+        priors: Vec<Option<Array1<f64>>>,
+    ) -> Result<Self, QuestPlusError>
+    where
+        Self: Sized;
+
+    /// Same as `new`, with the marginal priors given as distributions in the order of
+    /// `param_domain`. Each density is evaluated at the grid values of its parameter and then
+    /// normalized; `None` stands for a uniform prior.
+    fn from_distributions(
+        param_domain: &ParamDomain,
+        distributions: Vec<Option<&dyn Continuous<f64, f64>>>,
+    ) -> Result<Self, QuestPlusError>
+    where
+        Self: Sized,
+    {
+        if param_domain.params.len() != distributions.len() {
+            return Err(QuestPlusError::ParameterLengthNotMatch(
+                "param_domain".to_string(),
+                "distributions".to_string(),
+            ));
+        }
+        let mut priors = Vec::with_capacity(distributions.len());
+        for ((name, values), distribution) in param_domain.params.iter().zip(distributions) {
+            priors.push(density(name, values, distribution)?);
+        }
+        Self::new(param_domain, priors)
+    }
+
+    /// Same as `new`, with the priors given as a map of parameter name to prior. Parameters
+    /// left out of the map get a uniform prior.
+    fn from_map<K: Borrow<str> + Eq + Hash>(
+        param_domain: &ParamDomain,
+        mut priors: HashMap<K, Array1<f64>>,
+    ) -> Result<Self, QuestPlusError>
+    where
+        Self: Sized,
+    {
+        let names = param_domain.names();
+        let unknown = unknown_names(&names, priors.keys().map(|k| k.borrow()));
+        if !unknown.is_empty() {
+            let want = names.iter().map(|name| name.to_string()).collect();
+            return Err(QuestPlusError::ParameterNotExists(unknown, want));
+        }
+        let priors = names.iter().map(|name| priors.remove(*name)).collect();
+        Self::new(param_domain, priors)
+    }
+}
+
+impl PriorPDFFactory for ParamPDF {
+    /// Builds the joint prior of independent parameters from their marginal priors,
+    /// given in the order of `param_domain`. `None` stands for a uniform prior.
+    fn new(
+        param_domain: &ParamDomain,
+        priors: Vec<Option<Array1<f64>>>,
+    ) -> Result<Self, QuestPlusError> {
+        if param_domain.params.len() != priors.len() {
+            return Err(QuestPlusError::ParameterLengthNotMatch(
+                "param_domain".to_string(),
+                "priors".to_string(),
+            ));
+        }
+        let mut res: ArrayD<f64> = ArrayD::ones(IxDyn(&[]));
+        for ((name, values), prior) in param_domain.params.iter().zip(priors) {
+            let prior = normalized_prior(name, values, prior)?;
+            let shape: Vec<usize> = res
+                .shape()
+                .iter()
+                .cloned()
+                .chain(Some(prior.len()))
+                .collect();
+            let v = res
+                .iter()
+                .cartesian_product(prior.iter())
+                .map(|(a, b)| a * b)
+                .collect();
+            res = match ArrayD::from_shape_vec(IxDyn(&shape), v) {
+                Ok(a) => a,
+                Err(e) => return Err(QuestPlusError::NDArrayError(e)),
+            };
+        }
+        let sum = res.sum();
+        let res = res.mapv(|v| v / sum);
+        Ok(res)
+    }
+}
+
+/// Density of `distribution` at every value of the parameter `name`, or `None` without a
+/// distribution.
+pub fn density(
+    name: &str,
+    values: &Array1<f64>,
+    distribution: Option<&dyn Continuous<f64, f64>>,
+) -> Result<Option<Array1<f64>>, QuestPlusError> {
+    let distribution = match distribution {
+        Some(d) => d,
+        None => return Ok(None),
+    };
+    let prior = values.mapv(|v| distribution.pdf(v));
+    if prior.iter().any(|p| !p.is_finite() || *p < 0.) || prior.sum() <= 0. {
+        return Err(QuestPlusError::InvalidPrior(name.to_string()));
+    }
+    Ok(Some(prior))
+}
+
+/// Normalizes the prior of a single parameter, defaulting to a uniform prior over `domain`.
+fn normalized_prior(
+    name: &str,
+    domain: &Array1<f64>,
+    prior: Option<Array1<f64>>,
+) -> Result<Array1<f64>, QuestPlusError> {
+    let prior = match prior {
+        Some(s) => {
+            if domain.len() != s.len() {
+                return Err(QuestPlusError::ParameterLengthNotMatch(
+                    name.to_string(),
+                    format!("{}_prior", name),
+                ));
+            }
+            s
+        }
+        None => Array1::ones(domain.len()),
+    };
+    let sum = prior.sum();
+    Ok(prior.mapv(|v| v / sum))
+}
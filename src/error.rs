@@ -1,3 +1,4 @@
+use crate::StimScale;
 use std::collections::HashSet;
 use thiserror::Error;
 
@@ -5,10 +6,62 @@ use thiserror::Error;
 pub enum QuestPlusError {
     #[error("{0:?} not exists in {1:?}")]
     ParameterNotExists(HashSet<String>, HashSet<String>),
+    #[error("stim {0:?} not exists in stim domain")]
+    StimNotExists(Vec<f64>),
+    #[error("n of MinNEntropy must be positive, got {0}")]
+    InvalidMinNEntropyN(i32),
+    #[error("order of parameters {1:?} does not match {0:?}")]
+    ParameterOrderNotMatch(Vec<String>, Vec<String>),
+    #[error("outcome {0} not exists in outcome domain")]
+    OutcomeNotExists(String),
+    #[error("outcome {1} at stim {0:?} has zero likelihood under the posterior")]
+    ImpossibleOutcome(Vec<f64>, String),
+    #[error("probabilities of outcomes at stim {0:?} and params {1:?} do not sum to one")]
+    OutcomeProbabilityNotNormalized(Vec<f64>, Vec<f64>),
+    #[error("probabilities of outcomes at stim {0:?} and params {1:?} are not all in [0, 1]")]
+    OutcomeProbabilityOutOfRange(Vec<f64>, Vec<f64>),
+    #[error("credible mass must be in (0, 1), got {0}")]
+    InvalidCredibleMass(f64),
+    #[error("track {0} not exists")]
+    TrackNotExists(String),
+    #[error("track {0} already exists")]
+    TrackAlreadyExists(String),
+    #[error("invalid track weights: {0}")]
+    InvalidTrackWeight(String),
+    #[error("prior of {0} is not a finite, non-zero density over its domain")]
+    InvalidPrior(String),
+    #[error("{0} is required")]
+    MissingField(String),
+    #[error("domain of {0} is empty")]
+    EmptyDomain(String),
+    #[error("domain of {0} contains a value that is not finite")]
+    DomainNotFinite(String),
+    #[error("domain of {0} is not strictly increasing")]
+    DomainNotIncreasing(String),
+    #[error("{0} must be positive, got {1}")]
+    ParameterNotPositive(String, f64),
+    #[error("{0} must be in [0, 1), got {1}")]
+    ParameterOutOfRange(String, f64),
+    #[error("lower_asymptote {0} + lapse_rate {1} must be below 1")]
+    AsymptotesNotBelowOne(f64, f64),
+    #[error("shape of prior {0:?} does not match param domain {1:?}")]
+    PriorShapeNotMatch(Vec<usize>, Vec<usize>),
+    #[error("prior sums to {0} instead of one")]
+    PriorNotNormalized(f64),
     #[error("length of {0} and {1} does not match")]
     ParameterLengthNotMatch(String, String),
+    #[error("stim scale {0:?} is not supported, only Linear is")]
+    StimScaleNotSupported(StimScale),
     #[error("{0:?}")]
     NDArrayError(ndarray::ShapeError),
     #[error("{0:?}")]
     StatrsError(statrs::StatsError),
-}
\ No newline at end of file
+    #[error("{0:?}")]
+    IoError(std::io::Error),
+    #[cfg(feature = "serde")]
+    #[error("{0:?}")]
+    JsonError(serde_json::Error),
+    #[cfg(feature = "serde")]
+    #[error("{0:?}")]
+    BincodeError(bincode::Error),
+}
@@ -0,0 +1,170 @@
+//! Trial logs of a session as CSV or JSON, one row per trial.
+//!
+//! Columns are `trial` (starting at 1), one per stimulus dimension, `response`, `entropy` and one
+//! per parameter, named as in Python questplus (e.g. `intensity`, `mean`, `sd`,
+//! `lower_asymptote`, `lapse_rate`). `entropy` and the estimates are taken after the update of
+//! the trial.
+use crate::error::QuestPlusError;
+use crate::pf::PsychometricFunction;
+use crate::{Float, QuestPlus};
+use std::io::Write;
+
+enum Cell {
+    Number(f64),
+    Text(String),
+}
+
+/// Names of the columns of the trial log of `qp`.
+pub fn columns<P: PsychometricFunction, F: Float>(qp: &QuestPlus<P, F>) -> Vec<String> {
+    let mut columns = vec!["trial".to_string()];
+    columns.extend(qp.stim_domain.names().iter().map(|n| n.to_string()));
+    columns.push("response".to_string());
+    columns.push("entropy".to_string());
+    columns.extend(qp.param_domain.names().iter().map(|n| n.to_string()));
+    columns
+}
+
+fn rows<P: PsychometricFunction, F: Float>(
+    qp: &QuestPlus<P, F>,
+) -> Result<Vec<Vec<Cell>>, QuestPlusError> {
+    let n = qp.stim_history.len();
+    for (name, len) in [
+        ("resp_history", qp.resp_history.len()),
+        ("estimate_history", qp.estimate_history.len()),
+        ("entropy_history", qp.entropy_history.len()),
+    ] {
+        if len != n {
+            return Err(QuestPlusError::ParameterLengthNotMatch(
+                "stim_history".to_string(),
+                name.to_string(),
+            ));
+        }
+    }
+    let rows = (0..n)
+        .map(|i| {
+            let mut row = vec![Cell::Number((i + 1) as f64)];
+            row.extend(qp.stim_history[i].iter().map(|&v| Cell::Number(v)));
+            row.push(Cell::Text(format!("{:?}", qp.resp_history[i])));
+            row.push(Cell::Number(qp.entropy_history[i]));
+            row.extend(qp.estimate_history[i].iter().map(|&v| Cell::Number(v)));
+            row
+        })
+        .collect();
+    Ok(rows)
+}
+
+/// Writes the trial log of `qp` as CSV with a header row.
+pub fn write_csv<P: PsychometricFunction, F: Float, W: Write>(
+    qp: &QuestPlus<P, F>,
+    mut writer: W,
+) -> Result<(), QuestPlusError> {
+    let mut lines = vec![columns(qp).iter().map(|c| quote(c)).collect::<Vec<_>>()];
+    for row in rows(qp)? {
+        lines.push(
+            row.iter()
+                .map(|cell| match cell {
+                    Cell::Number(v) => v.to_string(),
+                    Cell::Text(s) => quote(s),
+                })
+                .collect(),
+        );
+    }
+    for line in lines {
+        writeln!(writer, "{}", line.join(",")).map_err(QuestPlusError::IoError)?;
+    }
+    Ok(())
+}
+
+/// Writes the trial log of `qp` as a JSON array of records, one object per trial keyed by
+/// column, as read by `pandas.read_json(orient="records")`.
+#[cfg(feature = "serde")]
+pub fn write_json<P: PsychometricFunction, F: Float, W: Write>(
+    qp: &QuestPlus<P, F>,
+    writer: W,
+) -> Result<(), QuestPlusError> {
+    use serde_json::{Map, Value};
+
+    let columns = columns(qp);
+    let records: Vec<Value> = rows(qp)?
+        .into_iter()
+        .map(|row| {
+            let record: Map<String, Value> = columns
+                .iter()
+                .cloned()
+                .zip(row.into_iter().map(|cell| match cell {
+                    Cell::Number(v) => Value::from(v),
+                    Cell::Text(s) => Value::from(s),
+                }))
+                .collect();
+            Value::Object(record)
+        })
+        .collect();
+    serde_json::to_writer(writer, &records).map_err(QuestPlusError::JsonError)
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::QuestPlusError;
+    use crate::export;
+    use crate::pf::Outcome;
+    use crate::testing;
+    use crate::StimSelectionMethod;
+
+    #[test]
+    fn test_write_csv() {
+        let mut qp = testing::norm_cdf(StimSelectionMethod::MinEntropy);
+        qp.update(6., Outcome::Correct).unwrap();
+        qp.update(3., Outcome::Incorrect).unwrap();
+
+        let mut buf = Vec::new();
+        export::write_csv(&qp, &mut buf).unwrap();
+        let csv = String::from_utf8(buf).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "trial,intensity,response,entropy,mean,sd,lower_asymptote,lapse_rate"
+        );
+        let row: Vec<&str> = lines[2].split(',').collect();
+        assert_eq!(&row[..3], &["2", "3", "Incorrect"]);
+        let entropy: f64 = row[3].parse().unwrap();
+        assert!((entropy - qp.entropy_history[1]).abs() < 1e-12);
+        let estimates: Vec<f64> = row[4..].iter().map(|v| v.parse().unwrap()).collect();
+        assert_eq!(estimates, qp.get_param_estimate_values());
+
+        qp.entropy_history.clear();
+        match export::write_csv(&qp, Vec::new()) {
+            Err(QuestPlusError::ParameterLengthNotMatch(_, name)) => {
+                assert_eq!(name, "entropy_history")
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_write_json() {
+        let mut qp = testing::norm_cdf(StimSelectionMethod::MinEntropy);
+        qp.update(6., Outcome::Correct).unwrap();
+
+        let mut buf = Vec::new();
+        export::write_json(&qp, &mut buf).unwrap();
+        let records: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        let records = records.as_array().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["trial"], 1.);
+        assert_eq!(records[0]["intensity"], 6.);
+        assert_eq!(records[0]["response"], "Correct");
+        assert_eq!(records[0]["entropy"], qp.entropy_history[0]);
+        assert_eq!(records[0]["sd"], qp.get_param_estimate_values()[1]);
+    }
+}
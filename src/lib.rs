@@ -1,74 +1,107 @@
+pub mod domain;
 pub mod error;
+pub mod export;
+pub mod manager;
 pub mod pf;
+pub mod qp;
+pub mod sim;
+#[cfg(test)]
+mod testing;
 
-use crate::error::QuestPlusError;
-use crate::pf::NormCDF;
-use itertools::iproduct;
-use itertools::Itertools;
-use ndarray::prelude::*;
-use ndarray::stack;
-use num::Float;
-use statrs::distribution::{Normal, Univariate};
-use std::collections::{HashMap, HashSet};
-use std::error::Error;
+pub use crate::qp::QuestPlus;
 
-#[derive(Debug)]
+use std::fmt::Debug;
+use std::iter::Sum;
+
+/// Floating-point type of the likelihood, prior and posterior tensors of `QuestPlus`. `f64` is
+/// the default; `f32` halves their memory footprint on large grids. Domains, prior factories,
+/// estimates and entropies stay `f64`, and priors are cast when an engine is built.
+pub trait Float: num::Float + Sum + Debug + Send + Sync + 'static {}
+
+impl<T: num::Float + Sum + Debug + Send + Sync + 'static> Float for T {}
+
+/// Converts an `f64` into the floating-point type of a posterior.
+pub(crate) fn cast<F: Float>(value: f64) -> F {
+    F::from(value).expect("f64 converts into any float type")
+}
+
+/// Units in which intensities and the location parameter of a psychometric function are given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StimScale {
     Linear,
     Log10,
     Decibel,
 }
 
+impl StimScale {
+    /// Converts `value` given in this scale into `to`.
+    pub fn convert(self, value: f64, to: StimScale) -> f64 {
+        match (self, to) {
+            (StimScale::Linear, StimScale::Log10) => value.log10(),
+            (StimScale::Linear, StimScale::Decibel) => 20. * value.log10(),
+            (StimScale::Log10, StimScale::Linear) => 10_f64.powf(value),
+            (StimScale::Log10, StimScale::Decibel) => 20. * value,
+            (StimScale::Decibel, StimScale::Linear) => 10_f64.powf(value / 20.),
+            (StimScale::Decibel, StimScale::Log10) => value / 20.,
+            _ => value,
+        }
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StimSelectionMethod {
     MinEntropy,
-    /* todo
     MinNEntropy(i32),
-     */
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ParamEstimationMethod {
     Mode,
     Mean,
 }
 
-trait QuestPlus {
-    type T1;
-    fn calc_pf(&self) -> Result<Self::T1, QuestPlusError>;
+/// Representation of the posterior used by `update` and stimulus selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PosteriorDomain {
+    /// Probabilities, normalized by division.
+    Linear,
+    /// Natural log of probabilities, normalized by log-sum-exp. Cells far below the mode do not
+    /// underflow to zero, so long sessions can still recover mass there.
+    Log,
 }
 
-impl QuestPlus for NormCDF {
-    type T1 = Array5<f64>;
+/// Criterion for ending a run, checked after every update.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StopRule {
+    /// At least this many trials have been run.
+    MaxTrials(usize),
+    /// Entropy of the posterior (in nats) is below this value.
+    EntropyBelow(f64),
+    /// Equal-tailed credible interval of `param` holding `mass` of its marginal posterior is
+    /// narrower than `width`.
+    CredibleIntervalWidth {
+        param: String,
+        mass: f64,
+        width: f64,
+    },
+    /// No parameter estimate has moved by more than `tolerance` over the last `trials` trials.
+    StableEstimate { trials: usize, tolerance: f64 },
+    /// Every rule has fired.
+    All(Vec<StopRule>),
+}
 
-    fn calc_pf(&self) -> Result<Self::T1, QuestPlusError> {
-        let num_elements = self.stim_domain.intensity.len()
-            * self.param_domain.mean.len()
-            * self.param_domain.sd.len()
-            * self.param_domain.lower_asymptote.len()
-            * self.param_domain.lapse_rate.len();
-        let mut v = Vec::with_capacity(num_elements);
-        for (x, m, s, la, lr) in iproduct!(
-            self.stim_domain.intensity.iter(),
-            self.param_domain.mean.iter(),
-            self.param_domain.sd.iter(),
-            self.param_domain.lower_asymptote.iter(),
-            self.param_domain.lapse_rate.iter()
-        ) {
-            v.push(NormCDF::f(*x, *m, *s, *la, *lr)?);
-        }
-        match Array5::from_shape_vec(
-            (
-                self.stim_domain.intensity.len(),
-                self.param_domain.mean.len(),
-                self.param_domain.sd.len(),
-                self.param_domain.lower_asymptote.len(),
-                self.param_domain.lapse_rate.len(),
-            ),
-            v,
-        ) {
-            Ok(a) => Ok(a),
-            Err(e) => Err(QuestPlusError::NDArrayError(e)),
-        }
-    }
+/// Rule that ended a run, with the value that satisfied it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StopReason {
+    MaxTrials(usize),
+    EntropyBelow(f64),
+    CredibleIntervalWidth(String, f64),
+    StableEstimate(usize),
+    All(Vec<StopReason>),
 }
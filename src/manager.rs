@@ -0,0 +1,232 @@
+use crate::domain::Stim;
+use crate::error::QuestPlusError;
+use crate::pf::PsychometricFunction;
+use crate::qp::QuestPlus;
+use crate::Float;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+
+/// How `Interleaved` picks the track that runs the next trial.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrackSelection {
+    Random,
+    RoundRobin,
+    Weighted,
+}
+
+/// A single QUEST+ track of an interleaved experiment.
+#[derive(Debug)]
+pub struct Track<P: PsychometricFunction, F: Float = f64> {
+    pub label: String,
+    pub qp: QuestPlus<P, F>,
+    pub max_trials: usize,
+    /// Relative probability of running this track under `TrackSelection::Weighted`.
+    pub weight: f64,
+}
+
+impl<P: PsychometricFunction, F: Float> Track<P, F> {
+    /// Whether the track has run `max_trials` trials or its engine hit a stop rule.
+    pub fn is_finished(&self) -> bool {
+        self.qp.stim_history.len() >= self.max_trials || self.qp.is_finished()
+    }
+}
+
+/// Runs several independent QUEST+ tracks interleaved, e.g. one per condition.
+#[derive(Debug)]
+pub struct Interleaved<P: PsychometricFunction, F: Float = f64> {
+    pub tracks: Vec<Track<P, F>>,
+    pub track_selection: TrackSelection,
+    pub rng: Pcg64,
+    last: Option<usize>,
+}
+
+impl<P: PsychometricFunction, F: Float> Interleaved<P, F> {
+    pub fn new(track_selection: TrackSelection) -> Self {
+        Interleaved {
+            tracks: Vec::new(),
+            track_selection,
+            rng: Pcg64::from_entropy(),
+            last: None,
+        }
+    }
+
+    /// Reseeds the random number generator used to pick tracks.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Pcg64::seed_from_u64(seed);
+    }
+
+    /// Adds a track with weight 1 that finishes after `max_trials` trials.
+    pub fn add_track(
+        &mut self,
+        label: &str,
+        qp: QuestPlus<P, F>,
+        max_trials: usize,
+    ) -> Result<(), QuestPlusError> {
+        if self.tracks.iter().any(|t| t.label == label) {
+            return Err(QuestPlusError::TrackAlreadyExists(label.to_string()));
+        }
+        self.tracks.push(Track {
+            label: label.to_string(),
+            qp,
+            max_trials,
+            weight: 1.,
+        });
+        Ok(())
+    }
+
+    pub fn track(&self, label: &str) -> Result<&Track<P, F>, QuestPlusError> {
+        match self.tracks.iter().find(|t| t.label == label) {
+            Some(t) => Ok(t),
+            None => Err(QuestPlusError::TrackNotExists(label.to_string())),
+        }
+    }
+
+    pub fn track_mut(&mut self, label: &str) -> Result<&mut Track<P, F>, QuestPlusError> {
+        match self.tracks.iter_mut().find(|t| t.label == label) {
+            Some(t) => Ok(t),
+            None => Err(QuestPlusError::TrackNotExists(label.to_string())),
+        }
+    }
+
+    /// Picks an unfinished track according to `track_selection` and returns its label together
+    /// with the stimulus it presents next, or `None` once every track is finished.
+    pub fn next_trial(&mut self) -> Result<Option<(String, Stim)>, QuestPlusError> {
+        let unfinished: Vec<usize> = (0..self.tracks.len())
+            .filter(|&i| !self.tracks[i].is_finished())
+            .collect();
+        if unfinished.is_empty() {
+            return Ok(None);
+        }
+        let index = match self.track_selection {
+            TrackSelection::Random => unfinished[self.rng.gen_range(0..unfinished.len())],
+            TrackSelection::RoundRobin => match self.last {
+                Some(last) => *unfinished
+                    .iter()
+                    .find(|&&i| i > last)
+                    .unwrap_or(&unfinished[0]),
+                None => unfinished[0],
+            },
+            TrackSelection::Weighted => {
+                let weights = unfinished.iter().map(|&i| self.tracks[i].weight);
+                match WeightedIndex::new(weights) {
+                    Ok(dist) => unfinished[dist.sample(&mut self.rng)],
+                    Err(e) => return Err(QuestPlusError::InvalidTrackWeight(e.to_string())),
+                }
+            }
+        };
+        self.last = Some(index);
+        let track = &mut self.tracks[index];
+        let stim = track.qp.next_stim()?;
+        Ok(Some((track.label.clone(), stim)))
+    }
+
+    /// Updates the posterior of the track `label` with the response observed at `stim`.
+    pub fn update<S: Into<Stim>>(
+        &mut self,
+        label: &str,
+        stim: S,
+        outcome: P::Outcome,
+    ) -> Result<(), QuestPlusError> {
+        self.track_mut(label)?.qp.update(stim, outcome)
+    }
+
+    /// Parameter estimates of every track, in the order the tracks were added.
+    pub fn get_param_estimates(&self) -> Vec<(String, P::Params)> {
+        self.tracks
+            .iter()
+            .map(|t| (t.label.clone(), t.qp.get_param_estimates()))
+            .collect()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tracks.iter().all(|t| t.is_finished())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::QuestPlusError;
+    use crate::manager::{Interleaved, TrackSelection};
+    use crate::pf::Outcome;
+    use crate::testing;
+    use crate::StimSelectionMethod;
+
+    #[test]
+    fn test_round_robin() {
+        let mut interleaved = Interleaved::new(TrackSelection::RoundRobin);
+        interleaved
+            .add_track(
+                "left",
+                testing::norm_cdf(StimSelectionMethod::MinEntropy),
+                3,
+            )
+            .unwrap();
+        interleaved
+            .add_track(
+                "right",
+                testing::norm_cdf(StimSelectionMethod::MinEntropy),
+                2,
+            )
+            .unwrap();
+        match interleaved.add_track(
+            "left",
+            testing::norm_cdf(StimSelectionMethod::MinEntropy),
+            1,
+        ) {
+            Err(QuestPlusError::TrackAlreadyExists(label)) => assert_eq!(label, "left"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let mut labels = Vec::new();
+        while let Some((label, stim)) = interleaved.next_trial().unwrap() {
+            let outcome = if label == "left" {
+                Outcome::Correct
+            } else {
+                Outcome::Incorrect
+            };
+            interleaved.update(&label, stim, outcome).unwrap();
+            labels.push(label);
+        }
+        assert_eq!(labels, vec!["left", "right", "left", "right", "left"]);
+        assert!(interleaved.is_finished());
+
+        let left = interleaved.track("left").unwrap();
+        assert_eq!(left.qp.resp_history, vec![Outcome::Correct; 3]);
+        let right = interleaved.track("right").unwrap();
+        assert_eq!(right.qp.resp_history, vec![Outcome::Incorrect; 2]);
+        let estimates = interleaved.get_param_estimates();
+        assert_eq!(estimates[0].0, "left");
+        assert!(estimates[0].1.mean < estimates[1].1.mean);
+
+        match interleaved.update("center", 14., Outcome::Correct) {
+            Err(QuestPlusError::TrackNotExists(label)) => assert_eq!(label, "center"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_weighted() {
+        let mut interleaved = Interleaved::new(TrackSelection::Weighted);
+        interleaved.seed(42);
+        interleaved
+            .add_track("a", testing::norm_cdf(StimSelectionMethod::MinEntropy), 10)
+            .unwrap();
+        interleaved
+            .add_track("b", testing::norm_cdf(StimSelectionMethod::MinEntropy), 10)
+            .unwrap();
+        interleaved.track_mut("b").unwrap().weight = 0.;
+        for _ in 0..10 {
+            let (label, stim) = interleaved.next_trial().unwrap().unwrap();
+            assert_eq!(label, "a");
+            interleaved.update(&label, stim, Outcome::Correct).unwrap();
+        }
+        assert!(interleaved.track("a").unwrap().is_finished());
+        assert!(!interleaved.is_finished());
+        match interleaved.next_trial() {
+            Err(QuestPlusError::InvalidTrackWeight(_)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
@@ -1,30 +1,56 @@
+use crate::domain::{ParamDomain, ParamPDF, PriorPDFFactory, StimDomain};
 use crate::error::QuestPlusError;
-use crate::{ParamEstimationMethod, StimSelectionMethod};
-use itertools::iproduct;
+use crate::qp::QuestPlus;
+use crate::{cast, Float, ParamEstimationMethod, StimScale, StimSelectionMethod};
 use ndarray::prelude::*;
-use ndarray::stack;
-use num::Float;
-use statrs::distribution::{Normal, Univariate};
-use std::error::Error;
+use statrs::distribution::{Continuous, Normal, Univariate};
+use std::fmt::Debug;
+use std::marker::PhantomData;
 
-#[derive(Debug)]
+pub mod logistic;
+pub mod weibull;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Outcome {
     Correct,
     Incorrect,
 }
 
-#[derive(Debug)]
-pub struct NormCDFStimDomain {
-    pub intensity: Array1<f64>,
-}
+/// A psychometric function that `QuestPlus` can run an experiment on.
+///
+/// Implementors must be `Sync` so that likelihoods can be evaluated across threads with the
+/// `parallel` feature.
+pub trait PsychometricFunction: Sync {
+    /// Typed parameter estimates returned by `QuestPlus::get_param_estimates`.
+    type Params;
+
+    /// Response to a single trial, e.g. `Outcome` for two-alternative tasks.
+    type Outcome: Copy + PartialEq + Debug;
 
-impl NormCDFStimDomain {
-    pub fn new(intensity: Array1<f64>) -> Self {
-        NormCDFStimDomain { intensity }
+    /// Names of the stimulus dimensions, in the order `eval` takes them.
+    fn stim_names(&self) -> Vec<&str> {
+        vec!["intensity"]
     }
+
+    /// Names of the parameters, in the order `eval` takes them.
+    fn param_names(&self) -> Vec<&str>;
+
+    /// Possible responses to a single trial.
+    fn outcomes(&self) -> Vec<Self::Outcome>;
+
+    /// Probability of every outcome, in the order of `outcomes`, at the given stimulus and
+    /// parameter point. The probabilities must each be in [0, 1] and sum to one.
+    fn eval(&self, stim: &[f64], params: &[f64]) -> Result<Vec<f64>, QuestPlusError>;
+
+    /// Converts parameter values, in the order of `param_names`, into typed parameters.
+    fn params(&self, values: &[f64]) -> Self::Params;
 }
 
-#[derive(Debug)]
+pub type NormCDFStimDomain = StimDomain;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NormCDFParamDomain {
     pub mean: Array1<f64>,
     pub sd: Array1<f64>,
@@ -48,6 +74,26 @@ impl NormCDFParamDomain {
     }
 }
 
+impl From<NormCDFParamDomain> for ParamDomain {
+    fn from(d: NormCDFParamDomain) -> Self {
+        ParamDomain::new(vec![
+            ("mean".to_string(), d.mean),
+            ("sd".to_string(), d.sd),
+            ("lower_asymptote".to_string(), d.lower_asymptote),
+            ("lapse_rate".to_string(), d.lapse_rate),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NormCDFParams {
+    pub mean: f64,
+    pub sd: f64,
+    pub lower_asymptote: f64,
+    pub lapse_rate: f64,
+}
+
 pub type NormCDFParamPDF = Array4<f64>;
 
 pub trait NormCDFPriorPDFFactory {
@@ -57,7 +103,22 @@ pub trait NormCDFPriorPDFFactory {
         sd: Option<Array1<f64>>,
         lower_asymptote: Option<Array1<f64>>,
         lapse_rate: Option<Array1<f64>>,
-    ) -> Result<Array4<f64>, QuestPlusError>;
+    ) -> Result<Self, QuestPlusError>
+    where
+        Self: Sized;
+
+    /// Joint prior with the marginal prior of each of `mean`, `sd`, `lower_asymptote` and
+    /// `lapse_rate` given as a distribution, e.g. `Normal` for `mean` and `LogNormal` for `sd`.
+    /// Parameters without a distribution get a uniform prior.
+    fn from_distributions(
+        param_domain: &NormCDFParamDomain,
+        mean: Option<&dyn Continuous<f64, f64>>,
+        sd: Option<&dyn Continuous<f64, f64>>,
+        lower_asymptote: Option<&dyn Continuous<f64, f64>>,
+        lapse_rate: Option<&dyn Continuous<f64, f64>>,
+    ) -> Result<Self, QuestPlusError>
+    where
+        Self: Sized;
 }
 
 impl NormCDFPriorPDFFactory for NormCDFParamPDF {
@@ -68,115 +129,123 @@ impl NormCDFPriorPDFFactory for NormCDFParamPDF {
         lower_asymptote: Option<Array1<f64>>,
         lapse_rate: Option<Array1<f64>>,
     ) -> Result<Self, QuestPlusError> {
-        let mean_prior = match mean {
-            Some(s) => {
-                if param_domain.mean.len() != s.len() {
-                    return Err(QuestPlusError::ParameterLengthNotMatch(
-                        "mean".to_string(),
-                        "mean_prior".to_string(),
-                    ));
-                }
-                s
-            }
-            None => Array1::ones(param_domain.mean.len()),
-        };
-        let sum = mean_prior.sum();
-        let mean_prior = mean_prior.mapv(|v| v / sum);
-
-        let sd_prior = match sd {
-            Some(s) => {
-                if param_domain.sd.len() != s.len() {
-                    return Err(QuestPlusError::ParameterLengthNotMatch(
-                        "sd".to_string(),
-                        "sd_prior".to_string(),
-                    ));
-                }
-                s
-            }
-            None => Array1::ones(param_domain.sd.len()),
-        };
-        let sum = sd_prior.sum();
-        let sd_prior = sd_prior.mapv(|v| v / sum);
-
-        let lower_asymptote_prior = match lower_asymptote {
-            Some(s) => {
-                if param_domain.lower_asymptote.len() != s.len() {
-                    return Err(QuestPlusError::ParameterLengthNotMatch(
-                        "lower_asymptote".to_string(),
-                        "lower_asymptote_prior".to_string(),
-                    ));
-                }
-                s
-            }
-            None => Array1::ones(param_domain.lower_asymptote.len()),
-        };
-        let sum = lower_asymptote_prior.sum();
-        let lower_asymptote_prior = lower_asymptote_prior.mapv(|v| v / sum);
-
-        let lapse_rate_prior = match lapse_rate {
-            Some(s) => {
-                if param_domain.lapse_rate.len() != s.len() {
-                    return Err(QuestPlusError::ParameterLengthNotMatch(
-                        "lapse_rate".to_string(),
-                        "lapse_rate_prior".to_string(),
-                    ));
-                }
-                s
-            }
-            None => Array1::ones(param_domain.lapse_rate.len()),
-        };
-        let sum = lapse_rate_prior.sum();
-        let lapse_rate_prior = lapse_rate_prior.mapv(|v| v / sum);
-
-        let mut v = Vec::with_capacity(
-            mean_prior.len()
-                * sd_prior.len()
-                * lower_asymptote_prior.len()
-                * lapse_rate_prior.len(),
-        );
-        for (m, s, la, lr) in iproduct!(
-            mean_prior.iter(),
-            sd_prior.iter(),
-            lower_asymptote_prior.iter(),
-            lapse_rate_prior.iter()
-        ) {
-            v.push(m * s * la * lr)
-        }
+        joint_prior_pdf(
+            [
+                ("mean", &param_domain.mean),
+                ("sd", &param_domain.sd),
+                ("lower_asymptote", &param_domain.lower_asymptote),
+                ("lapse_rate", &param_domain.lapse_rate),
+            ],
+            vec![mean, sd, lower_asymptote, lapse_rate],
+        )
+    }
 
-        let res = Array4::from_shape_vec(
-            (
-                mean_prior.len(),
-                sd_prior.len(),
-                lower_asymptote_prior.len(),
-                lapse_rate_prior.len(),
-            ),
-            v,
-        );
-        let res = match res {
-            Ok(a) => a,
-            Err(e) => return Err(QuestPlusError::NDArrayError(e)),
-        };
-        let sum = res.sum();
-        let res = res.mapv(|v| v / sum);
-        Ok(res)
+    fn from_distributions(
+        param_domain: &NormCDFParamDomain,
+        mean: Option<&dyn Continuous<f64, f64>>,
+        sd: Option<&dyn Continuous<f64, f64>>,
+        lower_asymptote: Option<&dyn Continuous<f64, f64>>,
+        lapse_rate: Option<&dyn Continuous<f64, f64>>,
+    ) -> Result<Self, QuestPlusError> {
+        joint_prior_pdf_from_distributions(
+            [
+                ("mean", &param_domain.mean),
+                ("sd", &param_domain.sd),
+                ("lower_asymptote", &param_domain.lower_asymptote),
+                ("lapse_rate", &param_domain.lapse_rate),
+            ],
+            vec![mean, sd, lower_asymptote, lapse_rate],
+        )
     }
 }
 
-#[derive(Debug)]
-pub struct NormCDF {
-    pub stim_domain: NormCDFStimDomain,
-    pub param_domain: NormCDFParamDomain,
-    pub prior_pdf: NormCDFParamPDF,
-    pub posterior_pdf: NormCDFParamPDF,
-    pub likelihoods: Array6<f64>,
-    pub stim_selection_method: StimSelectionMethod,
-    pub param_estimation_method: ParamEstimationMethod,
-    pub resp_history: Vec<f64>,
-    pub stim_history: Vec<f64>,
-    pub entropy: f64,
+/// Builds the joint prior of four independent parameters from their marginal priors.
+fn joint_prior_pdf(
+    param_domain: [(&str, &Array1<f64>); 4],
+    priors: Vec<Option<Array1<f64>>>,
+) -> Result<Array4<f64>, QuestPlusError> {
+    into_array4(ParamPDF::new(&four_param_domain(param_domain), priors)?)
+}
+
+/// Same as `joint_prior_pdf`, with the marginal priors given as distributions.
+fn joint_prior_pdf_from_distributions(
+    param_domain: [(&str, &Array1<f64>); 4],
+    distributions: Vec<Option<&dyn Continuous<f64, f64>>>,
+) -> Result<Array4<f64>, QuestPlusError> {
+    into_array4(ParamPDF::from_distributions(
+        &four_param_domain(param_domain),
+        distributions,
+    )?)
+}
+
+fn four_param_domain(param_domain: [(&str, &Array1<f64>); 4]) -> ParamDomain {
+    ParamDomain::new(
+        param_domain
+            .iter()
+            .map(|(name, values)| (name.to_string(), (*values).clone()))
+            .collect(),
+    )
+}
+
+fn into_array4(pdf: ParamPDF) -> Result<Array4<f64>, QuestPlusError> {
+    pdf.into_dimensionality::<Ix4>()
+        .map_err(QuestPlusError::NDArrayError)
+}
+
+/// Cumulative normal distribution on a linear intensity axis. As in Python questplus, only
+/// `StimScale::Linear` is supported; intensities already in log10 or dB units can be given as
+/// they are, with `mean` and `sd` in the same units.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NormCDFFunction {
+    pub stim_scale: StimScale,
+}
+
+impl NormCDFFunction {
+    pub fn new(stim_scale: StimScale) -> Self {
+        NormCDFFunction { stim_scale }
+    }
+}
+
+impl Default for NormCDFFunction {
+    fn default() -> Self {
+        NormCDFFunction::new(StimScale::Linear)
+    }
+}
+
+impl PsychometricFunction for NormCDFFunction {
+    type Params = NormCDFParams;
+    type Outcome = Outcome;
+
+    fn param_names(&self) -> Vec<&str> {
+        vec!["mean", "sd", "lower_asymptote", "lapse_rate"]
+    }
+
+    fn outcomes(&self) -> Vec<Outcome> {
+        vec![Outcome::Correct, Outcome::Incorrect]
+    }
+
+    fn eval(&self, stim: &[f64], params: &[f64]) -> Result<Vec<f64>, QuestPlusError> {
+        if self.stim_scale != StimScale::Linear {
+            return Err(QuestPlusError::StimScaleNotSupported(self.stim_scale));
+        }
+        let p = NormCDF::f(stim[0], params[0], params[1], params[2], params[3])?;
+        Ok(vec![p, 1. - p])
+    }
+
+    fn params(&self, values: &[f64]) -> NormCDFParams {
+        NormCDFParams {
+            mean: values[0],
+            sd: values[1],
+            lower_asymptote: values[2],
+            lapse_rate: values[3],
+        }
+    }
 }
 
-impl NormCDF {
+pub type NormCDF = QuestPlus<NormCDFFunction>;
+
+impl<F: Float> QuestPlus<NormCDFFunction, F> {
     pub fn new(
         stim_domain: NormCDFStimDomain,
         param_domain: NormCDFParamDomain,
@@ -184,23 +253,14 @@ impl NormCDF {
         stim_selection_method: StimSelectionMethod,
         param_estimation_method: ParamEstimationMethod,
     ) -> Result<Self, QuestPlusError> {
-        let likelihoods = Self::gen_likelihoods(&stim_domain, &param_domain)?;
-        let posterior_pdf = prior_pdf.clone();
-        let resp_history = Vec::new();
-        let stim_history = Vec::new();
-        let entropy = f64::max_value();
-        Ok(NormCDF {
+        QuestPlus::with_pf(
+            NormCDFFunction::default(),
             stim_domain,
-            param_domain,
-            prior_pdf,
-            posterior_pdf,
-            likelihoods,
+            param_domain.into(),
+            prior_pdf.into_dyn().mapv(cast),
             stim_selection_method,
             param_estimation_method,
-            resp_history,
-            stim_history,
-            entropy,
-        })
+        )
     }
 
     pub fn f(
@@ -217,61 +277,159 @@ impl NormCDF {
         Ok(lower_asymptote + (1.0 - lower_asymptote - lapse_rate) * norm.cdf(intensity))
     }
 
-    fn apply_fields(
-        stim_domain: &NormCDFStimDomain,
-        param_domain: &NormCDFParamDomain,
-    ) -> Result<Array5<f64>, QuestPlusError> {
-        let num_elements = stim_domain.intensity.len()
-            * param_domain.mean.len()
-            * param_domain.sd.len()
-            * param_domain.lower_asymptote.len()
-            * param_domain.lapse_rate.len();
-        let mut v = Vec::with_capacity(num_elements);
-        for (x, m, s, la, lr) in iproduct!(
-            stim_domain.intensity.iter(),
-            param_domain.mean.iter(),
-            param_domain.sd.iter(),
-            param_domain.lower_asymptote.iter(),
-            param_domain.lapse_rate.iter()
-        ) {
-            v.push(NormCDF::f(*x, *m, *s, *la, *lr)?);
-        }
-        match Array5::from_shape_vec(
-            (
-                stim_domain.intensity.len(),
-                param_domain.mean.len(),
-                param_domain.sd.len(),
-                param_domain.lower_asymptote.len(),
-                param_domain.lapse_rate.len(),
-            ),
-            v,
-        ) {
-            Ok(a) => Ok(a),
-            Err(e) => Err(QuestPlusError::NDArrayError(e)),
+    pub fn builder() -> NormCDFBuilder<F> {
+        NormCDFBuilder::default()
+    }
+}
+
+/// Builder for `NormCDF` that validates the whole configuration before building the engine.
+///
+/// Without a prior the builder uses a uniform one. Stimuli are selected by `MinEntropy` and
+/// parameters estimated by `Mode` unless set otherwise.
+#[derive(Debug)]
+pub struct NormCDFBuilder<F: Float = f64> {
+    stim_domain: Option<NormCDFStimDomain>,
+    param_domain: Option<NormCDFParamDomain>,
+    prior_pdf: Option<NormCDFParamPDF>,
+    stim_scale: Option<StimScale>,
+    stim_selection_method: Option<StimSelectionMethod>,
+    param_estimation_method: Option<ParamEstimationMethod>,
+    float: PhantomData<F>,
+}
+
+impl<F: Float> Default for NormCDFBuilder<F> {
+    fn default() -> Self {
+        NormCDFBuilder {
+            stim_domain: None,
+            param_domain: None,
+            prior_pdf: None,
+            stim_scale: None,
+            stim_selection_method: None,
+            param_estimation_method: None,
+            float: PhantomData,
         }
     }
+}
 
-    fn gen_likelihoods(
-        stim_domain: &NormCDFStimDomain,
-        param_domain: &NormCDFParamDomain,
-    ) -> Result<Array6<f64>, QuestPlusError> {
-        let prop_correct = Self::apply_fields(stim_domain, param_domain)?;
-        let prop_incorrect = prop_correct.mapv(|v| 1. - v);
-        let pf_values = stack(Axis(0), &[prop_correct.view(), prop_incorrect.view()]);
-        match pf_values {
-            Ok(a) => Ok(a),
-            Err(e) => Err(QuestPlusError::NDArrayError(e)),
+impl<F: Float> NormCDFBuilder<F> {
+    pub fn stim_domain(mut self, stim_domain: NormCDFStimDomain) -> Self {
+        self.stim_domain = Some(stim_domain);
+        self
+    }
+
+    pub fn param_domain(mut self, param_domain: NormCDFParamDomain) -> Self {
+        self.param_domain = Some(param_domain);
+        self
+    }
+
+    pub fn prior_pdf(mut self, prior_pdf: NormCDFParamPDF) -> Self {
+        self.prior_pdf = Some(prior_pdf);
+        self
+    }
+
+    pub fn stim_scale(mut self, stim_scale: StimScale) -> Self {
+        self.stim_scale = Some(stim_scale);
+        self
+    }
+
+    pub fn stim_selection_method(mut self, stim_selection_method: StimSelectionMethod) -> Self {
+        self.stim_selection_method = Some(stim_selection_method);
+        self
+    }
+
+    pub fn param_estimation_method(
+        mut self,
+        param_estimation_method: ParamEstimationMethod,
+    ) -> Self {
+        self.param_estimation_method = Some(param_estimation_method);
+        self
+    }
+
+    pub fn build(self) -> Result<QuestPlus<NormCDFFunction, F>, QuestPlusError> {
+        let stim_domain = match self.stim_domain {
+            Some(d) => d,
+            None => return Err(QuestPlusError::MissingField("stim_domain".to_string())),
+        };
+        let param_domain = match self.param_domain {
+            Some(d) => d,
+            None => return Err(QuestPlusError::MissingField("param_domain".to_string())),
+        };
+        let stim_scale = self.stim_scale.unwrap_or(StimScale::Linear);
+        if stim_scale != StimScale::Linear {
+            return Err(QuestPlusError::StimScaleNotSupported(stim_scale));
         }
+        stim_domain.validate()?;
+        let domain: ParamDomain = param_domain.clone().into();
+        domain.validate()?;
+        if let Some(&sd) = param_domain.sd.iter().find(|&&sd| sd <= 0.) {
+            return Err(QuestPlusError::ParameterNotPositive("sd".to_string(), sd));
+        }
+        for (name, values) in [
+            ("lower_asymptote", &param_domain.lower_asymptote),
+            ("lapse_rate", &param_domain.lapse_rate),
+        ] {
+            if let Some(&v) = values.iter().find(|&&v| !(0. ..1.).contains(&v)) {
+                return Err(QuestPlusError::ParameterOutOfRange(name.to_string(), v));
+            }
+        }
+        let lower_asymptote = param_domain
+            .lower_asymptote
+            .fold(f64::MIN, |a, &b| a.max(b));
+        let lapse_rate = param_domain.lapse_rate.fold(f64::MIN, |a, &b| a.max(b));
+        if lower_asymptote + lapse_rate >= 1. {
+            return Err(QuestPlusError::AsymptotesNotBelowOne(
+                lower_asymptote,
+                lapse_rate,
+            ));
+        }
+
+        let prior_pdf = match self.prior_pdf {
+            Some(p) => p,
+            None => NormCDFParamPDF::new(&param_domain, None, None, None, None)?,
+        };
+        if prior_pdf.shape() != domain.shape().as_slice() {
+            return Err(QuestPlusError::PriorShapeNotMatch(
+                prior_pdf.shape().to_vec(),
+                domain.shape(),
+            ));
+        }
+        if prior_pdf.iter().any(|p| !p.is_finite() || *p < 0.) {
+            return Err(QuestPlusError::InvalidPrior("prior_pdf".to_string()));
+        }
+        let sum = prior_pdf.sum();
+        if (sum - 1.).abs() > 1e-9 {
+            return Err(QuestPlusError::PriorNotNormalized(sum));
+        }
+
+        QuestPlus::with_pf(
+            NormCDFFunction::new(stim_scale),
+            stim_domain,
+            domain,
+            prior_pdf.into_dyn().mapv(cast),
+            self.stim_selection_method
+                .unwrap_or(StimSelectionMethod::MinEntropy),
+            self.param_estimation_method
+                .unwrap_or(ParamEstimationMethod::Mode),
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::domain::Stim;
+    use crate::error::QuestPlusError;
     use crate::pf::{
-        NormCDF, NormCDFParamDomain, NormCDFParamPDF, NormCDFPriorPDFFactory, NormCDFStimDomain,
+        NormCDF, NormCDFFunction, NormCDFParamDomain, NormCDFParamPDF, NormCDFParams,
+        NormCDFPriorPDFFactory, NormCDFStimDomain, Outcome,
     };
-    use crate::{ParamEstimationMethod, QuestPlus, StimSelectionMethod};
+    use crate::qp::QuestPlus;
+    use crate::testing;
+    #[cfg(feature = "serde")]
+    use crate::PosteriorDomain;
+    use crate::{ParamEstimationMethod, StimScale, StimSelectionMethod};
+    use approx::AbsDiffEq;
     use ndarray::prelude::*;
+    use statrs::distribution::{Beta, Continuous, LogNormal, Normal};
 
     #[test]
     fn test_norm_cdf() {
@@ -508,8 +666,7 @@ mod tests {
             ),
             want_vec,
         )
-        .unwrap()
-        .into();
+        .unwrap();
 
         let stim_domain = NormCDFStimDomain::new(intensity);
         let param_domain = NormCDFParamDomain::new(mean, sd, lower_asymptote, lapse_rate);
@@ -524,10 +681,179 @@ mod tests {
         )
         .unwrap();
 
-        let result = norm_cdf.calc_pf().unwrap();
+        let result = norm_cdf.likelihoods.index_axis(Axis(0), 0);
         println!("{:?}", result);
 
-        assert!(result.all_close(&want, 1e-8));
+        assert!(result.abs_diff_eq(&want.into_dyn(), 1e-8));
+    }
+
+    #[test]
+    fn test_prior_from_distributions() {
+        let mean: Array1<f64> = Array1::range(5., 25., 5.);
+        let sd: Array1<f64> = arr1(&[1., 2., 4.]);
+        let lower_asymptote: Array1<f64> = arr1(&[0.5]);
+        let lapse_rate: Array1<f64> = arr1(&[0.01, 0.02, 0.05]);
+        let param_domain = NormCDFParamDomain::new(mean, sd, lower_asymptote, lapse_rate);
+
+        let normal = Normal::new(15., 5.).unwrap();
+        let log_normal = LogNormal::new(0.5, 1.).unwrap();
+        let beta = Beta::new(2., 50.).unwrap();
+        let prior_pdf = NormCDFParamPDF::from_distributions(
+            &param_domain,
+            Some(&normal),
+            Some(&log_normal),
+            None,
+            Some(&beta),
+        )
+        .unwrap();
+
+        let want = NormCDFParamPDF::new(
+            &param_domain,
+            Some(param_domain.mean.mapv(|v| normal.pdf(v))),
+            Some(param_domain.sd.mapv(|v| log_normal.pdf(v))),
+            None,
+            Some(param_domain.lapse_rate.mapv(|v| beta.pdf(v))),
+        )
+        .unwrap();
+        assert!(prior_pdf.abs_diff_eq(&want, 1e-15));
+        assert!((prior_pdf.sum() - 1.).abs() < 1e-12);
+
+        let far = Normal::new(1000., 1.).unwrap();
+        match NormCDFParamPDF::from_distributions(&param_domain, Some(&far), None, None, None) {
+            Err(QuestPlusError::InvalidPrior(name)) => assert_eq!(name, "mean"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_builder() {
+        let (stim_domain, param_domain) = testing::norm_cdf_domains();
+
+        let mut norm_cdf = NormCDF::builder()
+            .stim_domain(stim_domain.clone())
+            .param_domain(param_domain.clone())
+            .param_estimation_method(ParamEstimationMethod::Mean)
+            .build()
+            .unwrap();
+        let stim = norm_cdf.next_stim().unwrap();
+        assert_eq!(stim[0], 14.);
+        assert!((norm_cdf.entropy - 1.9513254297077054).abs() < 1e-10);
+
+        let build = |stim_domain: NormCDFStimDomain, param_domain: NormCDFParamDomain| {
+            NormCDF::builder()
+                .stim_domain(stim_domain)
+                .param_domain(param_domain)
+                .build()
+        };
+        let with = |f: &dyn Fn(&mut NormCDFParamDomain)| {
+            let mut d = param_domain.clone();
+            f(&mut d);
+            d
+        };
+        match NormCDF::builder()
+            .param_domain(param_domain.clone())
+            .build()
+        {
+            Err(QuestPlusError::MissingField(name)) => assert_eq!(name, "stim_domain"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match build(NormCDFStimDomain::new(arr1(&[])), param_domain.clone()) {
+            Err(QuestPlusError::EmptyDomain(name)) => assert_eq!(name, "intensity"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match build(
+            stim_domain.clone(),
+            with(&|d| d.mean = arr1(&[5., f64::NAN])),
+        ) {
+            Err(QuestPlusError::DomainNotFinite(name)) => assert_eq!(name, "mean"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match build(stim_domain.clone(), with(&|d| d.sd = arr1(&[4., 2.]))) {
+            Err(QuestPlusError::DomainNotIncreasing(name)) => assert_eq!(name, "sd"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match build(
+            stim_domain.clone(),
+            with(&|d| d.lapse_rate = arr1(&[0.01, 0.01])),
+        ) {
+            Err(QuestPlusError::DomainNotIncreasing(name)) => assert_eq!(name, "lapse_rate"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match build(stim_domain.clone(), with(&|d| d.sd = arr1(&[0., 2.]))) {
+            Err(QuestPlusError::ParameterNotPositive(name, sd)) => {
+                assert_eq!(name, "sd");
+                assert_eq!(sd, 0.);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match build(
+            stim_domain.clone(),
+            with(&|d| d.lapse_rate = arr1(&[0.2, 0.5])),
+        ) {
+            Err(QuestPlusError::AsymptotesNotBelowOne(la, lr)) => assert_eq!((la, lr), (0.5, 0.5)),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        match build(stim_domain.clone(), with(&|d| d.lapse_rate = arr1(&[-0.2]))) {
+            Err(QuestPlusError::ParameterOutOfRange(name, lr)) => {
+                assert_eq!(name, "lapse_rate");
+                assert_eq!(lr, -0.2);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match build(
+            stim_domain.clone(),
+            with(&|d| {
+                d.lower_asymptote = arr1(&[1.5]);
+                d.lapse_rate = arr1(&[-0.6]);
+            }),
+        ) {
+            Err(QuestPlusError::ParameterOutOfRange(name, la)) => {
+                assert_eq!(name, "lower_asymptote");
+                assert_eq!(la, 1.5);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let builder = || {
+            NormCDF::builder()
+                .stim_domain(stim_domain.clone())
+                .param_domain(param_domain.clone())
+        };
+        match builder().prior_pdf(Array4::ones((4, 2, 1, 2))).build() {
+            Err(QuestPlusError::PriorShapeNotMatch(got, want)) => {
+                assert_eq!(got, vec![4, 2, 1, 2]);
+                assert_eq!(want, vec![4, 2, 1, 1]);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+        let mut prior_pdf: NormCDFParamPDF = Array4::from_elem((4, 2, 1, 1), 0.125);
+        prior_pdf[[0, 0, 0, 0]] = -0.125;
+        match builder().prior_pdf(prior_pdf).build() {
+            Err(QuestPlusError::InvalidPrior(name)) => assert_eq!(name, "prior_pdf"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match builder().prior_pdf(Array4::ones((4, 2, 1, 1))).build() {
+            Err(QuestPlusError::PriorNotNormalized(sum)) => assert_eq!(sum, 8.),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let single = QuestPlus::<NormCDFFunction, f32>::builder()
+            .stim_domain(stim_domain.clone())
+            .param_domain(param_domain.clone())
+            .param_estimation_method(ParamEstimationMethod::Mean)
+            .build()
+            .unwrap();
+        let likelihoods = single.likelihoods.mapv(f64::from);
+        assert!(likelihoods.abs_diff_eq(&norm_cdf.likelihoods, 1e-6));
+        match QuestPlus::<NormCDFFunction, f32>::builder()
+            .stim_domain(stim_domain.clone())
+            .param_domain(with(&|d| d.sd = arr1(&[0., 2.])))
+            .build()
+        {
+            Err(QuestPlusError::ParameterNotPositive(name, _)) => assert_eq!(name, "sd"),
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
     }
 
     #[test]
@@ -541,8 +867,283 @@ mod tests {
 
         let stim_domain = NormCDFStimDomain::new(intensity);
         let param_domain = NormCDFParamDomain::new(mean, sd, lower_asymptote, lapse_rate);
+        let norm_cdf =
+            testing::norm_cdf_from(stim_domain, param_domain, StimSelectionMethod::MinEntropy)
+                .unwrap();
+        dbg!(norm_cdf);
+    }
+
+    #[test]
+    fn test_update() {
+        let intensity: Array1<f64> = Array1::range(0., 50., 1.);
+        let mean: Array1<f64> = Array1::range(7., 9., 1.);
+        let sd: Array1<f64> = Array1::range(7., 8., 0.5);
+        let lower_asymptote: Array1<f64> = arr1(&[0.5]);
+        let lapse_rate: Array1<f64> = Array1::range(0.01, 0.02, 0.01);
+
+        let stim_domain = NormCDFStimDomain::new(intensity);
+        let param_domain = NormCDFParamDomain::new(mean, sd, lower_asymptote, lapse_rate);
+        let mut norm_cdf =
+            testing::norm_cdf_from(stim_domain, param_domain, StimSelectionMethod::MinEntropy)
+                .unwrap();
+
+        norm_cdf.update(10., Outcome::Correct).unwrap();
+        norm_cdf.update(3., Outcome::Incorrect).unwrap();
+
+        let correct = norm_cdf.likelihoods.slice(s![0, 10, .., .., .., ..]);
+        let incorrect = norm_cdf.likelihoods.slice(s![1, 3, .., .., .., ..]);
+        let want = &norm_cdf.prior_pdf * &correct * incorrect;
+        let sum = want.sum();
+        let want = want.mapv(|v| v / sum);
+
+        assert!(norm_cdf.posterior_pdf.abs_diff_eq(&want, 1e-12));
+        assert!((norm_cdf.posterior_pdf.sum() - 1.).abs() < 1e-12);
+        assert_eq!(norm_cdf.stim_history, vec![Stim::from(10.), Stim::from(3.)]);
+        assert_eq!(
+            norm_cdf.resp_history,
+            vec![Outcome::Correct, Outcome::Incorrect]
+        );
+
+        match norm_cdf.update(0.5, Outcome::Correct) {
+            Err(QuestPlusError::StimNotExists(x)) => assert_eq!(x, vec![0.5]),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        assert_eq!(norm_cdf.stim_history.len(), 2);
+    }
+
+    #[test]
+    fn test_next_stim() {
+        let mut norm_cdf = testing::norm_cdf(StimSelectionMethod::MinEntropy);
+
+        // want values are generated by Python code
+        let stim = norm_cdf.next_stim().unwrap();
+        assert_eq!(stim[0], 14.);
+        assert!((norm_cdf.entropy - 1.9513254297077054).abs() < 1e-10);
+
+        norm_cdf.update(stim, Outcome::Correct).unwrap();
+        norm_cdf.update(3., Outcome::Incorrect).unwrap();
+        let stim = norm_cdf.next_stim().unwrap();
+        assert_eq!(stim[0], 13.);
+        assert!((norm_cdf.entropy - 1.9313673462136092).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_next_stim_f32() {
+        let (stim_domain, param_domain) = testing::norm_cdf_domains();
         let prior_pdf = NormCDFParamPDF::new(&param_domain, None, None, None, None).unwrap();
 
+        let mut norm_cdf = QuestPlus::<NormCDFFunction, f32>::new(
+            stim_domain,
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mean,
+        )
+        .unwrap();
+        assert_eq!(norm_cdf.likelihoods.shape(), &[2, 30, 4, 2, 1, 1]);
+
+        // same reference values as test_next_stim, at single precision
+        let stim = norm_cdf.next_stim().unwrap();
+        assert_eq!(stim[0], 14.);
+        assert!((norm_cdf.entropy - 1.9513254297077054).abs() < 1e-5);
+
+        norm_cdf.update(stim, Outcome::Correct).unwrap();
+        norm_cdf.update(3., Outcome::Incorrect).unwrap();
+        let stim = norm_cdf.next_stim().unwrap();
+        assert_eq!(stim[0], 13.);
+        assert!((norm_cdf.entropy - 1.9313673462136092).abs() < 1e-5);
+        assert!((norm_cdf.posterior_pdf.sum() - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_f32_agrees_with_f64() {
+        let (stim_domain, param_domain) = testing::norm_cdf_domains();
+        let prior_pdf = NormCDFParamPDF::new(
+            &param_domain,
+            Some(arr1(&[0.1, 0.2, 0.6, 0.1])),
+            Some(arr1(&[0.25, 0.75])),
+            None,
+            None,
+        )
+        .unwrap();
+
+        let mut double = QuestPlus::<NormCDFFunction, f64>::new(
+            stim_domain.clone(),
+            param_domain.clone(),
+            prior_pdf.clone(),
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mean,
+        )
+        .unwrap();
+        let mut single = QuestPlus::<NormCDFFunction, f32>::new(
+            stim_domain,
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mean,
+        )
+        .unwrap();
+        for outcome in [
+            Outcome::Correct,
+            Outcome::Incorrect,
+            Outcome::Correct,
+            Outcome::Correct,
+            Outcome::Incorrect,
+        ] {
+            let stim = double.next_stim().unwrap();
+            assert_eq!(single.next_stim().unwrap(), stim);
+            double.update(stim.clone(), outcome).unwrap();
+            single.update(stim, outcome).unwrap();
+
+            let posterior_pdf = single.posterior_pdf.mapv(f64::from);
+            assert!(posterior_pdf.abs_diff_eq(&double.posterior_pdf, 1e-5));
+            for (s, d) in single
+                .get_param_estimate_values()
+                .iter()
+                .zip(double.get_param_estimate_values())
+            {
+                assert!((s - d).abs() < 1e-4);
+            }
+        }
+        double.param_estimation_method = ParamEstimationMethod::Mode;
+        single.param_estimation_method = ParamEstimationMethod::Mode;
+        assert_eq!(single.get_param_estimates(), double.get_param_estimates());
+    }
+
+    #[test]
+    fn test_next_stim_min_n_entropy() {
+        let mut norm_cdf = testing::norm_cdf(StimSelectionMethod::MinNEntropy(3));
+
+        // the three intensities with the lowest expected entropy under the uniform prior
+        let candidates = [13., 14., 15.];
+        norm_cdf.seed(42);
+        let stims: Vec<f64> = (0..20).map(|_| norm_cdf.next_stim().unwrap()[0]).collect();
+        assert!(stims.iter().all(|x| candidates.contains(x)));
+        assert!(candidates.iter().all(|x| stims.contains(x)));
+
+        norm_cdf.seed(42);
+        let replayed: Vec<f64> = (0..20).map(|_| norm_cdf.next_stim().unwrap()[0]).collect();
+        assert_eq!(stims, replayed);
+
+        norm_cdf.stim_selection_method = StimSelectionMethod::MinNEntropy(1);
+        assert_eq!(norm_cdf.next_stim().unwrap()[0], 14.);
+        assert!((norm_cdf.entropy - 1.9513254297077054).abs() < 1e-10);
+
+        norm_cdf.stim_selection_method = StimSelectionMethod::MinNEntropy(0);
+        match norm_cdf.next_stim() {
+            Err(QuestPlusError::InvalidMinNEntropyN(n)) => assert_eq!(n, 0),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let (stim_domain, param_domain) = testing::norm_cdf_domains();
+        match testing::norm_cdf_from(
+            stim_domain,
+            param_domain,
+            StimSelectionMethod::MinNEntropy(-1),
+        ) {
+            Err(QuestPlusError::InvalidMinNEntropyN(n)) => assert_eq!(n, -1),
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_save_and_resume() {
+        let mut norm_cdf = testing::norm_cdf(StimSelectionMethod::MinNEntropy(3));
+        norm_cdf.seed(7);
+        for outcome in [Outcome::Correct, Outcome::Incorrect, Outcome::Correct].iter() {
+            let stim = norm_cdf.next_stim().unwrap();
+            norm_cdf.update(stim, *outcome).unwrap();
+        }
+
+        let json = norm_cdf.to_json().unwrap();
+        assert!(!json.contains("likelihoods"));
+        let bytes = norm_cdf.to_bincode().unwrap();
+        for mut resumed in [
+            NormCDF::from_json(&json).unwrap(),
+            NormCDF::from_bincode(&bytes).unwrap(),
+        ] {
+            assert_eq!(resumed.likelihoods, norm_cdf.likelihoods);
+            assert_eq!(resumed.posterior_pdf, norm_cdf.posterior_pdf);
+            assert_eq!(resumed.stim_history, norm_cdf.stim_history);
+            assert_eq!(resumed.resp_history, norm_cdf.resp_history);
+            assert_eq!(resumed.entropy, norm_cdf.entropy);
+            assert_eq!(
+                resumed.get_param_estimates(),
+                norm_cdf.get_param_estimates()
+            );
+            let mut original = NormCDF::from_bincode(&bytes).unwrap();
+            for _ in 0..5 {
+                assert_eq!(resumed.next_stim().unwrap(), original.next_stim().unwrap());
+            }
+        }
+
+        norm_cdf.set_posterior_domain(PosteriorDomain::Log);
+        let resumed = NormCDF::from_json(&norm_cdf.to_json().unwrap()).unwrap();
+        assert_eq!(resumed.posterior_domain, PosteriorDomain::Log);
+        assert_eq!(resumed.likelihoods, norm_cdf.likelihoods);
+        assert_eq!(resumed.posterior_pdf, norm_cdf.posterior_pdf);
+
+        match NormCDF::from_json("{}") {
+            Err(QuestPlusError::JsonError(_)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let new_norm_cdf = || testing::norm_cdf(StimSelectionMethod::MinEntropy);
+
+        let mut live = new_norm_cdf();
+        let outcomes = [
+            Outcome::Correct,
+            Outcome::Correct,
+            Outcome::Incorrect,
+            Outcome::Correct,
+            Outcome::Incorrect,
+        ];
+        for outcome in outcomes.iter() {
+            let stim = live.next_stim().unwrap();
+            live.update(stim, *outcome).unwrap();
+        }
+        let trials: Vec<(f64, Outcome)> = live
+            .stim_history
+            .iter()
+            .map(|stim| stim[0])
+            .zip(live.resp_history.iter().cloned())
+            .collect();
+
+        let mut replayed = new_norm_cdf();
+        replayed.update(3., Outcome::Incorrect).unwrap();
+        replayed.replay(trials.clone()).unwrap();
+        assert_eq!(replayed.posterior_pdf, live.posterior_pdf);
+        assert_eq!(replayed.stim_history, live.stim_history);
+        assert_eq!(replayed.resp_history, live.resp_history);
+        assert_eq!(replayed.entropy, live.entropy);
+
+        match replayed.replay(vec![(3., Outcome::Correct), (0.5, Outcome::Correct)]) {
+            Err(QuestPlusError::StimNotExists(x)) => assert_eq!(x, vec![0.5]),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        assert_eq!(replayed.posterior_pdf, live.posterior_pdf);
+        assert_eq!(replayed.stim_history, live.stim_history);
+        assert_eq!(replayed.resp_history, live.resp_history);
+        assert_eq!(replayed.estimate_history, live.estimate_history);
+        assert_eq!(replayed.entropy, live.entropy);
+    }
+
+    #[test]
+    fn test_marginal_posterior() {
+        let (stim_domain, param_domain) = testing::norm_cdf_domains();
+        let prior_pdf = NormCDFParamPDF::new(
+            &param_domain,
+            Some(arr1(&[0.1, 0.2, 0.6, 0.1])),
+            Some(arr1(&[0.25, 0.75])),
+            None,
+            None,
+        )
+        .unwrap();
+
         let norm_cdf = NormCDF::new(
             stim_domain,
             param_domain,
@@ -551,6 +1152,138 @@ mod tests {
             ParamEstimationMethod::Mean,
         )
         .unwrap();
-        dbg!(norm_cdf);
+
+        let mean_pdf = norm_cdf.marginal_posterior_pdf("mean").unwrap();
+        assert!(mean_pdf.abs_diff_eq(&arr1(&[0.1, 0.2, 0.6, 0.1]), 1e-12));
+        let sd_pdf = norm_cdf.marginal_posterior_pdf("sd").unwrap();
+        assert!(sd_pdf.abs_diff_eq(&arr1(&[0.25, 0.75]), 1e-12));
+
+        assert!((norm_cdf.posterior_sd("mean").unwrap() - 15.25_f64.sqrt()).abs() < 1e-10);
+        assert!((norm_cdf.posterior_sd("sd").unwrap() - 0.75_f64.sqrt()).abs() < 1e-10);
+        assert!(norm_cdf.posterior_sd("lapse_rate").unwrap().abs() < 1e-10);
+
+        assert_eq!(norm_cdf.credible_interval("mean", 0.6).unwrap(), (10., 15.));
+        assert_eq!(norm_cdf.credible_interval("sd", 0.4).unwrap(), (4., 4.));
+        assert_eq!(
+            norm_cdf.highest_density_interval("mean", 0.75).unwrap(),
+            (10., 15.)
+        );
+        assert_eq!(
+            norm_cdf.highest_density_interval("mean", 0.5).unwrap(),
+            (15., 15.)
+        );
+
+        match norm_cdf.credible_interval("midpoint", 0.95) {
+            Err(QuestPlusError::ParameterNotExists(_, _)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match norm_cdf.highest_density_interval("mean", 0.) {
+            Err(QuestPlusError::InvalidCredibleMass(m)) => assert_eq!(m, 0.),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_params_of_interest() {
+        let (stim_domain, mut param_domain) = testing::norm_cdf_domains();
+        param_domain.lapse_rate = arr1(&[0.01, 0.05]);
+        let mut norm_cdf =
+            testing::norm_cdf_from(stim_domain, param_domain, StimSelectionMethod::MinEntropy)
+                .unwrap();
+        norm_cdf.next_stim().unwrap();
+        let joint_entropy = norm_cdf.entropy;
+
+        match norm_cdf.set_params_of_interest(&["mean", "slope"]) {
+            Err(QuestPlusError::ParameterNotExists(unknown, _)) => {
+                assert!(unknown.contains("slope"))
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+        assert!(norm_cdf.params_of_interest.is_empty());
+
+        // marginalizing out a nuisance axis of two values removes up to ln 2 nats
+        norm_cdf.set_params_of_interest(&["mean", "sd"]).unwrap();
+        norm_cdf.next_stim().unwrap();
+        assert!(norm_cdf.entropy < joint_entropy);
+        assert!(norm_cdf.entropy > joint_entropy - 2_f64.ln() - 1e-10);
+
+        for _ in 0..10 {
+            let stim = norm_cdf.next_stim().unwrap();
+            let outcome = if stim[0] >= 15. {
+                Outcome::Correct
+            } else {
+                Outcome::Incorrect
+            };
+            norm_cdf.update(stim, outcome).unwrap();
+        }
+        assert!((norm_cdf.get_param_estimates().mean - 15.).abs() < 2.5);
+    }
+
+    #[test]
+    fn test_get_param_estimates() {
+        let (stim_domain, mut param_domain) = testing::norm_cdf_domains();
+        param_domain.lapse_rate = arr1(&[0.01, 0.05]);
+        let mut norm_cdf =
+            testing::norm_cdf_from(stim_domain, param_domain, StimSelectionMethod::MinEntropy)
+                .unwrap();
+        for (x, outcome) in [
+            (14., Outcome::Correct),
+            (3., Outcome::Incorrect),
+            (12., Outcome::Correct),
+            (8., Outcome::Incorrect),
+            (25., Outcome::Correct),
+        ]
+        .iter()
+        {
+            norm_cdf.update(*x, *outcome).unwrap();
+        }
+
+        // want values are generated by Python code
+        let estimates = norm_cdf.get_param_estimates();
+        assert!((estimates.mean - 12.68693816266908).abs() < 1e-10);
+        assert!((estimates.sd - 2.9705811084035023).abs() < 1e-10);
+        assert!((estimates.lower_asymptote - 0.5).abs() < 1e-10);
+        assert!((estimates.lapse_rate - 0.029583585825396504).abs() < 1e-10);
+
+        norm_cdf.param_estimation_method = ParamEstimationMethod::Mode;
+        assert_eq!(
+            norm_cdf.get_param_estimates(),
+            NormCDFParams {
+                mean: 10.,
+                sd: 2.,
+                lower_asymptote: 0.5,
+                lapse_rate: 0.01,
+            }
+        );
+    }
+
+    #[test]
+    fn test_norm_cdf_stim_scale() {
+        for stim_scale in [StimScale::Log10, StimScale::Decibel] {
+            let (stim_domain, param_domain) = testing::norm_cdf_domains();
+            let prior_pdf = NormCDFParamPDF::new(&param_domain, None, None, None, None).unwrap();
+            let result: Result<NormCDF, _> = QuestPlus::with_pf(
+                NormCDFFunction::new(stim_scale),
+                stim_domain.clone(),
+                param_domain.clone().into(),
+                prior_pdf.into_dyn(),
+                StimSelectionMethod::MinEntropy,
+                ParamEstimationMethod::Mean,
+            );
+            match result {
+                Err(QuestPlusError::StimScaleNotSupported(s)) => assert_eq!(s, stim_scale),
+                r => panic!("unexpected result: {:?}", r.map(|_| ())),
+            }
+
+            let result = NormCDF::builder()
+                .stim_domain(stim_domain)
+                .param_domain(param_domain)
+                .stim_scale(stim_scale)
+                .build();
+            match result {
+                Err(QuestPlusError::StimScaleNotSupported(s)) => assert_eq!(s, stim_scale),
+                r => panic!("unexpected result: {:?}", r.map(|_| ())),
+            }
+        }
     }
 }
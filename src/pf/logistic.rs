@@ -0,0 +1,395 @@
+use crate::domain::{ParamDomain, StimDomain};
+use crate::error::QuestPlusError;
+use crate::pf::{
+    joint_prior_pdf, joint_prior_pdf_from_distributions, Outcome, PsychometricFunction,
+};
+use crate::qp::QuestPlus;
+use crate::{cast, Float, ParamEstimationMethod, StimScale, StimSelectionMethod};
+use ndarray::prelude::*;
+use statrs::distribution::Continuous;
+
+pub type LogisticStimDomain = StimDomain;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LogisticParamDomain {
+    pub midpoint: Array1<f64>,
+    pub slope: Array1<f64>,
+    pub lower_asymptote: Array1<f64>,
+    pub lapse_rate: Array1<f64>,
+}
+
+impl LogisticParamDomain {
+    pub fn new(
+        midpoint: Array1<f64>,
+        slope: Array1<f64>,
+        lower_asymptote: Array1<f64>,
+        lapse_rate: Array1<f64>,
+    ) -> Self {
+        LogisticParamDomain {
+            midpoint,
+            slope,
+            lower_asymptote,
+            lapse_rate,
+        }
+    }
+}
+
+impl From<LogisticParamDomain> for ParamDomain {
+    fn from(d: LogisticParamDomain) -> Self {
+        ParamDomain::new(vec![
+            ("midpoint".to_string(), d.midpoint),
+            ("slope".to_string(), d.slope),
+            ("lower_asymptote".to_string(), d.lower_asymptote),
+            ("lapse_rate".to_string(), d.lapse_rate),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LogisticParams {
+    pub midpoint: f64,
+    pub slope: f64,
+    pub lower_asymptote: f64,
+    pub lapse_rate: f64,
+}
+
+pub type LogisticParamPDF = Array4<f64>;
+
+pub trait LogisticPriorPDFFactory {
+    fn new(
+        param_domain: &LogisticParamDomain,
+        midpoint: Option<Array1<f64>>,
+        slope: Option<Array1<f64>>,
+        lower_asymptote: Option<Array1<f64>>,
+        lapse_rate: Option<Array1<f64>>,
+    ) -> Result<Self, QuestPlusError>
+    where
+        Self: Sized;
+
+    /// Joint prior from a distribution per logistic parameter, e.g. a `Beta` for
+    /// `lower_asymptote` and `lapse_rate`, which must stay below one. `None` keeps a parameter
+    /// uniform.
+    fn from_distributions(
+        param_domain: &LogisticParamDomain,
+        midpoint: Option<&dyn Continuous<f64, f64>>,
+        slope: Option<&dyn Continuous<f64, f64>>,
+        lower_asymptote: Option<&dyn Continuous<f64, f64>>,
+        lapse_rate: Option<&dyn Continuous<f64, f64>>,
+    ) -> Result<Self, QuestPlusError>
+    where
+        Self: Sized;
+}
+
+impl LogisticPriorPDFFactory for LogisticParamPDF {
+    fn new(
+        param_domain: &LogisticParamDomain,
+        midpoint: Option<Array1<f64>>,
+        slope: Option<Array1<f64>>,
+        lower_asymptote: Option<Array1<f64>>,
+        lapse_rate: Option<Array1<f64>>,
+    ) -> Result<Self, QuestPlusError> {
+        joint_prior_pdf(
+            [
+                ("midpoint", &param_domain.midpoint),
+                ("slope", &param_domain.slope),
+                ("lower_asymptote", &param_domain.lower_asymptote),
+                ("lapse_rate", &param_domain.lapse_rate),
+            ],
+            vec![midpoint, slope, lower_asymptote, lapse_rate],
+        )
+    }
+
+    fn from_distributions(
+        param_domain: &LogisticParamDomain,
+        midpoint: Option<&dyn Continuous<f64, f64>>,
+        slope: Option<&dyn Continuous<f64, f64>>,
+        lower_asymptote: Option<&dyn Continuous<f64, f64>>,
+        lapse_rate: Option<&dyn Continuous<f64, f64>>,
+    ) -> Result<Self, QuestPlusError> {
+        joint_prior_pdf_from_distributions(
+            [
+                ("midpoint", &param_domain.midpoint),
+                ("slope", &param_domain.slope),
+                ("lower_asymptote", &param_domain.lower_asymptote),
+                ("lapse_rate", &param_domain.lapse_rate),
+            ],
+            vec![midpoint, slope, lower_asymptote, lapse_rate],
+        )
+    }
+}
+
+/// Logistic function on a linear intensity axis. Only `StimScale::Linear` is supported;
+/// intensities already in log10 or dB units can be given as they are, with `midpoint` in the
+/// same units and `slope` per unit.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LogisticFunction {
+    pub stim_scale: StimScale,
+}
+
+impl LogisticFunction {
+    pub fn new(stim_scale: StimScale) -> Self {
+        LogisticFunction { stim_scale }
+    }
+}
+
+impl Default for LogisticFunction {
+    fn default() -> Self {
+        LogisticFunction::new(StimScale::Linear)
+    }
+}
+
+impl PsychometricFunction for LogisticFunction {
+    type Params = LogisticParams;
+    type Outcome = Outcome;
+
+    fn param_names(&self) -> Vec<&str> {
+        vec!["midpoint", "slope", "lower_asymptote", "lapse_rate"]
+    }
+
+    fn outcomes(&self) -> Vec<Outcome> {
+        vec![Outcome::Correct, Outcome::Incorrect]
+    }
+
+    fn eval(&self, stim: &[f64], params: &[f64]) -> Result<Vec<f64>, QuestPlusError> {
+        if self.stim_scale != StimScale::Linear {
+            return Err(QuestPlusError::StimScaleNotSupported(self.stim_scale));
+        }
+        let p = Logistic::f(stim[0], params[0], params[1], params[2], params[3])?;
+        Ok(vec![p, 1. - p])
+    }
+
+    fn params(&self, values: &[f64]) -> LogisticParams {
+        LogisticParams {
+            midpoint: values[0],
+            slope: values[1],
+            lower_asymptote: values[2],
+            lapse_rate: values[3],
+        }
+    }
+}
+
+pub type Logistic = QuestPlus<LogisticFunction>;
+
+impl<F: Float> QuestPlus<LogisticFunction, F> {
+    pub fn new(
+        stim_domain: LogisticStimDomain,
+        param_domain: LogisticParamDomain,
+        prior_pdf: LogisticParamPDF,
+        stim_selection_method: StimSelectionMethod,
+        param_estimation_method: ParamEstimationMethod,
+    ) -> Result<Self, QuestPlusError> {
+        QuestPlus::with_pf(
+            LogisticFunction::default(),
+            stim_domain,
+            param_domain.into(),
+            prior_pdf.into_dyn().mapv(cast),
+            stim_selection_method,
+            param_estimation_method,
+        )
+    }
+
+    pub fn f(
+        intensity: f64,
+        midpoint: f64,
+        slope: f64,
+        lower_asymptote: f64,
+        lapse_rate: f64,
+    ) -> Result<f64, QuestPlusError> {
+        let p = 1. / (1. + (-slope * (intensity - midpoint)).exp());
+        Ok(lower_asymptote + (1.0 - lower_asymptote - lapse_rate) * p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::QuestPlusError;
+    use crate::pf::logistic::LogisticFunction;
+    use crate::pf::logistic::{
+        Logistic, LogisticParamDomain, LogisticParamPDF, LogisticParams, LogisticPriorPDFFactory,
+        LogisticStimDomain,
+    };
+    use crate::pf::Outcome;
+    use crate::qp::QuestPlus;
+    use crate::{ParamEstimationMethod, StimScale, StimSelectionMethod};
+    use approx::AbsDiffEq;
+    use ndarray::prelude::*;
+
+    #[test]
+    fn test_logistic() {
+        let intensity: Array1<f64> = Array1::range(-10., 11., 2.);
+        let midpoint: Array1<f64> = arr1(&[-1., 1.]);
+        let slope: Array1<f64> = arr1(&[0.5, 1.]);
+        let lower_asymptote: Array1<f64> = arr1(&[0.5]);
+        let lapse_rate: Array1<f64> = arr1(&[0.01]);
+        // want_slice is generated by Python code
+        let want_slice = [
+            [
+                [[[0.5053836018889907]], [[0.5000604633422333]]],
+                [[[0.5019943674807891]], [[0.5000081836967055]]],
+            ],
+            [
+                [[[0.5143629930681646]], [[0.5004464150852563]]],
+                [[[0.5053836018889907]], [[0.5000604633422333]]],
+            ],
+            [
+                [[[0.5371705082104093]], [[0.5032794969528995]]],
+                [[[0.5143629930681646]], [[0.5004464150852563]]],
+            ],
+            [
+                [[[0.5893885066651146]], [[0.5232386778570077]]],
+                [[[0.5371705082104093]], [[0.5032794969528995]]],
+            ],
+            [
+                [[[0.6849949277110913]], [[0.6317812964712977]]],
+                [[[0.5893885066651146]], [[0.5232386778570077]]],
+            ],
+            [
+                [[[0.8050050722889087]], [[0.8582187035287023]]],
+                [[[0.6849949277110913]], [[0.6317812964712977]]],
+            ],
+            [
+                [[[0.9006114933348854]], [[0.9667613221429923]]],
+                [[[0.8050050722889087]], [[0.8582187035287023]]],
+            ],
+            [
+                [[[0.9528294917895908]], [[0.9867205030471005]]],
+                [[[0.9006114933348854]], [[0.9667613221429923]]],
+            ],
+            [
+                [[[0.9756370069318354]], [[0.9895535849147437]]],
+                [[[0.9528294917895908]], [[0.9867205030471005]]],
+            ],
+            [
+                [[[0.9846163981110094]], [[0.9899395366577667]]],
+                [[[0.9756370069318354]], [[0.9895535849147437]]],
+            ],
+            [
+                [[[0.9880056325192109]], [[0.9899918163032945]]],
+                [[[0.9846163981110094]], [[0.9899395366577667]]],
+            ],
+        ];
+        let mut want_vec = Vec::new();
+        for a in want_slice.iter() {
+            for b in a.iter() {
+                for c in b.iter() {
+                    for d in c.iter() {
+                        for e in d.iter() {
+                            want_vec.push(*e);
+                        }
+                    }
+                }
+            }
+        }
+        let want: Array5<f64> = Array::from_shape_vec(
+            (
+                intensity.len(),
+                midpoint.len(),
+                slope.len(),
+                lower_asymptote.len(),
+                lapse_rate.len(),
+            ),
+            want_vec,
+        )
+        .unwrap();
+
+        let stim_domain = LogisticStimDomain::new(intensity);
+        let param_domain = LogisticParamDomain::new(midpoint, slope, lower_asymptote, lapse_rate);
+        let prior_pdf = LogisticParamPDF::new(&param_domain, None, None, None, None).unwrap();
+
+        let logistic = Logistic::new(
+            stim_domain,
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mean,
+        )
+        .unwrap();
+
+        let result = logistic.likelihoods.index_axis(Axis(0), 0);
+        assert!(result.abs_diff_eq(&want.clone().into_dyn(), 1e-8));
+        let result = logistic.likelihoods.index_axis(Axis(0), 1);
+        assert!(result.abs_diff_eq(&want.mapv(|v| 1. - v).into_dyn(), 1e-8));
+    }
+
+    #[test]
+    fn test_logistic_session() {
+        let intensity: Array1<f64> = Array1::range(-10., 11., 1.);
+        let midpoint: Array1<f64> = Array1::range(-4., 5., 2.);
+        let slope: Array1<f64> = arr1(&[0.5, 2.]);
+        let lower_asymptote: Array1<f64> = arr1(&[0., 0.5]);
+        let lapse_rate: Array1<f64> = arr1(&[0.01]);
+
+        let stim_domain = LogisticStimDomain::new(intensity);
+        let param_domain = LogisticParamDomain::new(midpoint, slope, lower_asymptote, lapse_rate);
+        let prior_pdf = LogisticParamPDF::new(&param_domain, None, None, None, None).unwrap();
+
+        let mut logistic = Logistic::new(
+            stim_domain,
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mean,
+        )
+        .unwrap();
+
+        // want values are generated by Python code
+        assert_eq!(logistic.next_stim().unwrap()[0], -1.);
+        assert!((logistic.entropy - 2.7366347019209805).abs() < 1e-10);
+
+        for (x, outcome) in [
+            (0., Outcome::Correct),
+            (-3., Outcome::Incorrect),
+            (2., Outcome::Correct),
+            (-1., Outcome::Incorrect),
+            (5., Outcome::Correct),
+            (-6., Outcome::Incorrect),
+        ]
+        .iter()
+        {
+            logistic.update(*x, *outcome).unwrap();
+        }
+        assert_eq!(logistic.next_stim().unwrap()[0], -10.);
+        assert!((logistic.entropy - 2.10064093374682).abs() < 1e-10);
+
+        let estimates = logistic.get_param_estimates();
+        assert!((estimates.midpoint - -0.06749094505498843).abs() < 1e-10);
+        assert!((estimates.slope - 1.334108241978697).abs() < 1e-10);
+        assert!((estimates.lower_asymptote - 0.12070278489929391).abs() < 1e-10);
+        assert!((estimates.lapse_rate - 0.01).abs() < 1e-10);
+
+        logistic.param_estimation_method = ParamEstimationMethod::Mode;
+        assert_eq!(
+            logistic.get_param_estimates(),
+            LogisticParams {
+                midpoint: 0.,
+                slope: 2.,
+                lower_asymptote: 0.,
+                lapse_rate: 0.01,
+            }
+        );
+    }
+
+    #[test]
+    fn test_logistic_stim_scale() {
+        for stim_scale in [StimScale::Log10, StimScale::Decibel] {
+            let param_domain =
+                LogisticParamDomain::new(arr1(&[-2.]), arr1(&[10.]), arr1(&[0.5]), arr1(&[0.01]));
+            let prior_pdf = LogisticParamPDF::new(&param_domain, None, None, None, None).unwrap();
+            let result: Result<Logistic, _> = QuestPlus::with_pf(
+                LogisticFunction::new(stim_scale),
+                LogisticStimDomain::new(arr1(&[-2.2, -2., -1.9, -1.])),
+                param_domain.into(),
+                prior_pdf.into_dyn(),
+                StimSelectionMethod::MinEntropy,
+                ParamEstimationMethod::Mean,
+            );
+            match result {
+                Err(QuestPlusError::StimScaleNotSupported(s)) => assert_eq!(s, stim_scale),
+                r => panic!("unexpected result: {:?}", r.map(|_| ())),
+            }
+        }
+    }
+}
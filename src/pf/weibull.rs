@@ -0,0 +1,483 @@
+use crate::domain::{ParamDomain, StimDomain};
+use crate::error::QuestPlusError;
+use crate::pf::{
+    joint_prior_pdf, joint_prior_pdf_from_distributions, Outcome, PsychometricFunction,
+};
+use crate::qp::QuestPlus;
+use crate::{cast, Float, ParamEstimationMethod, StimScale, StimSelectionMethod};
+use ndarray::prelude::*;
+use statrs::distribution::Continuous;
+
+pub type WeibullStimDomain = StimDomain;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WeibullParamDomain {
+    pub threshold: Array1<f64>,
+    pub slope: Array1<f64>,
+    pub lower_asymptote: Array1<f64>,
+    pub lapse_rate: Array1<f64>,
+}
+
+impl WeibullParamDomain {
+    pub fn new(
+        threshold: Array1<f64>,
+        slope: Array1<f64>,
+        lower_asymptote: Array1<f64>,
+        lapse_rate: Array1<f64>,
+    ) -> Self {
+        WeibullParamDomain {
+            threshold,
+            slope,
+            lower_asymptote,
+            lapse_rate,
+        }
+    }
+}
+
+impl From<WeibullParamDomain> for ParamDomain {
+    fn from(d: WeibullParamDomain) -> Self {
+        ParamDomain::new(vec![
+            ("threshold".to_string(), d.threshold),
+            ("slope".to_string(), d.slope),
+            ("lower_asymptote".to_string(), d.lower_asymptote),
+            ("lapse_rate".to_string(), d.lapse_rate),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WeibullParams {
+    pub threshold: f64,
+    pub slope: f64,
+    pub lower_asymptote: f64,
+    pub lapse_rate: f64,
+}
+
+pub type WeibullParamPDF = Array4<f64>;
+
+pub trait WeibullPriorPDFFactory {
+    fn new(
+        param_domain: &WeibullParamDomain,
+        threshold: Option<Array1<f64>>,
+        slope: Option<Array1<f64>>,
+        lower_asymptote: Option<Array1<f64>>,
+        lapse_rate: Option<Array1<f64>>,
+    ) -> Result<Self, QuestPlusError>
+    where
+        Self: Sized;
+
+    /// Joint prior from a distribution per Weibull parameter. `threshold` is evaluated in
+    /// `stim_scale` units, so on the default `Log10` scale a `Normal` over it is log-normal in
+    /// linear units. `None` keeps a parameter uniform.
+    fn from_distributions(
+        param_domain: &WeibullParamDomain,
+        threshold: Option<&dyn Continuous<f64, f64>>,
+        slope: Option<&dyn Continuous<f64, f64>>,
+        lower_asymptote: Option<&dyn Continuous<f64, f64>>,
+        lapse_rate: Option<&dyn Continuous<f64, f64>>,
+    ) -> Result<Self, QuestPlusError>
+    where
+        Self: Sized;
+}
+
+impl WeibullPriorPDFFactory for WeibullParamPDF {
+    fn new(
+        param_domain: &WeibullParamDomain,
+        threshold: Option<Array1<f64>>,
+        slope: Option<Array1<f64>>,
+        lower_asymptote: Option<Array1<f64>>,
+        lapse_rate: Option<Array1<f64>>,
+    ) -> Result<Self, QuestPlusError> {
+        joint_prior_pdf(
+            [
+                ("threshold", &param_domain.threshold),
+                ("slope", &param_domain.slope),
+                ("lower_asymptote", &param_domain.lower_asymptote),
+                ("lapse_rate", &param_domain.lapse_rate),
+            ],
+            vec![threshold, slope, lower_asymptote, lapse_rate],
+        )
+    }
+
+    fn from_distributions(
+        param_domain: &WeibullParamDomain,
+        threshold: Option<&dyn Continuous<f64, f64>>,
+        slope: Option<&dyn Continuous<f64, f64>>,
+        lower_asymptote: Option<&dyn Continuous<f64, f64>>,
+        lapse_rate: Option<&dyn Continuous<f64, f64>>,
+    ) -> Result<Self, QuestPlusError> {
+        joint_prior_pdf_from_distributions(
+            [
+                ("threshold", &param_domain.threshold),
+                ("slope", &param_domain.slope),
+                ("lower_asymptote", &param_domain.lower_asymptote),
+                ("lapse_rate", &param_domain.lapse_rate),
+            ],
+            vec![threshold, slope, lower_asymptote, lapse_rate],
+        )
+    }
+}
+
+/// Weibull function evaluated on a log10 intensity axis. Intensities and `threshold` are
+/// given in `stim_scale` units, so `Linear` gives `1 - exp(-(x / threshold)^slope)`, which
+/// requires positive intensities and thresholds, and `Decibel` gives
+/// `1 - exp(-10^(slope * (x - threshold) / 20))`.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WeibullFunction {
+    pub stim_scale: StimScale,
+}
+
+impl WeibullFunction {
+    pub fn new(stim_scale: StimScale) -> Self {
+        WeibullFunction { stim_scale }
+    }
+}
+
+impl Default for WeibullFunction {
+    fn default() -> Self {
+        WeibullFunction::new(StimScale::Log10)
+    }
+}
+
+impl PsychometricFunction for WeibullFunction {
+    type Params = WeibullParams;
+    type Outcome = Outcome;
+
+    fn param_names(&self) -> Vec<&str> {
+        vec!["threshold", "slope", "lower_asymptote", "lapse_rate"]
+    }
+
+    fn outcomes(&self) -> Vec<Outcome> {
+        vec![Outcome::Correct, Outcome::Incorrect]
+    }
+
+    fn eval(&self, stim: &[f64], params: &[f64]) -> Result<Vec<f64>, QuestPlusError> {
+        if self.stim_scale == StimScale::Linear {
+            for (name, v) in [("intensity", stim[0]), ("threshold", params[0])] {
+                if v <= 0. {
+                    return Err(QuestPlusError::ParameterNotPositive(name.to_string(), v));
+                }
+            }
+        }
+        let p = Weibull::f(
+            self.stim_scale.convert(stim[0], StimScale::Log10),
+            self.stim_scale.convert(params[0], StimScale::Log10),
+            params[1],
+            params[2],
+            params[3],
+        )?;
+        Ok(vec![p, 1. - p])
+    }
+
+    fn params(&self, values: &[f64]) -> WeibullParams {
+        WeibullParams {
+            threshold: values[0],
+            slope: values[1],
+            lower_asymptote: values[2],
+            lapse_rate: values[3],
+        }
+    }
+}
+
+pub type Weibull = QuestPlus<WeibullFunction>;
+
+impl<F: Float> QuestPlus<WeibullFunction, F> {
+    pub fn new(
+        stim_domain: WeibullStimDomain,
+        param_domain: WeibullParamDomain,
+        prior_pdf: WeibullParamPDF,
+        stim_selection_method: StimSelectionMethod,
+        param_estimation_method: ParamEstimationMethod,
+    ) -> Result<Self, QuestPlusError> {
+        QuestPlus::with_pf(
+            WeibullFunction::default(),
+            stim_domain,
+            param_domain.into(),
+            prior_pdf.into_dyn().mapv(cast),
+            stim_selection_method,
+            param_estimation_method,
+        )
+    }
+
+    /// Weibull function with intensity and threshold given in log10 units.
+    pub fn f(
+        intensity: f64,
+        threshold: f64,
+        slope: f64,
+        lower_asymptote: f64,
+        lapse_rate: f64,
+    ) -> Result<f64, QuestPlusError> {
+        let p = 1. - (-(10_f64.powf(slope * (intensity - threshold)))).exp();
+        Ok(lower_asymptote + (1.0 - lower_asymptote - lapse_rate) * p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{ParamDomain, ParamPDF, PriorPDFFactory};
+    use crate::error::QuestPlusError;
+    use crate::pf::weibull::WeibullFunction;
+    use crate::pf::weibull::{
+        Weibull, WeibullParamDomain, WeibullParamPDF, WeibullParams, WeibullPriorPDFFactory,
+        WeibullStimDomain,
+    };
+    use crate::pf::Outcome;
+    use crate::qp::QuestPlus;
+    use crate::{ParamEstimationMethod, StimScale, StimSelectionMethod};
+    use approx::AbsDiffEq;
+    use ndarray::prelude::*;
+
+    #[test]
+    fn test_weibull() {
+        let intensity: Array1<f64> = Array1::range(-3.5, -0.4, 0.25);
+        let threshold: Array1<f64> = arr1(&[-2., -1.5]);
+        let slope: Array1<f64> = arr1(&[3., 3.5]);
+        let lower_asymptote: Array1<f64> = arr1(&[0.5]);
+        let lapse_rate: Array1<f64> = arr1(&[0.01]);
+        // want_slice is generated by Python code
+        let want_slice = [
+            [
+                [[[0.5000154949155374]], [[0.5000027554647459]]],
+                [[[0.500000489999755]], [[0.5000000489999975]]],
+            ],
+            [
+                [[[0.5000871279439709]], [[0.5000206626929957]]],
+                [[[0.5000027554647459]], [[0.5000003674480248]]],
+            ],
+            [
+                [[[0.5004897550816463]], [[0.5001549271079305]]],
+                [[[0.5000154949155374]], [[0.5000027554647459]]],
+            ],
+            [
+                [[[0.5027477394153868]], [[0.5011605964679247]]],
+                [[[0.5000871279439709]], [[0.5000206626929957]]],
+            ],
+            [
+                [[[0.5152527227733631]], [[0.5086365505175053]]],
+                [[[0.5004897550816463]], [[0.5001549271079305]]],
+            ],
+            [
+                [[[0.5798276447179068]], [[0.561173139927883]]],
+                [[[0.5027477394153868]], [[0.5011605964679247]]],
+            ],
+            [
+                [[[0.8097390738259933]], [[0.8097390738259933]]],
+                [[[0.5152527227733631]], [[0.5086365505175053]]],
+            ],
+            [
+                [[[0.9882299777109304]], [[0.9897287018022135]]],
+                [[[0.5798276447179068]], [[0.561173139927883]]],
+            ],
+            [
+                [[[0.9899999999999909]], [[0.99]]],
+                [[[0.8097390738259933]], [[0.8097390738259933]]],
+            ],
+            [
+                [[[0.99]], [[0.99]]],
+                [[[0.9882299777109304]], [[0.9897287018022135]]],
+            ],
+            [[[[0.99]], [[0.99]]], [[[0.9899999999999909]], [[0.99]]]],
+            [[[[0.99]], [[0.99]]], [[[0.99]], [[0.99]]]],
+            [[[[0.99]], [[0.99]]], [[[0.99]], [[0.99]]]],
+        ];
+        let mut want_vec = Vec::new();
+        for a in want_slice.iter() {
+            for b in a.iter() {
+                for c in b.iter() {
+                    for d in c.iter() {
+                        for e in d.iter() {
+                            want_vec.push(*e);
+                        }
+                    }
+                }
+            }
+        }
+        let want: Array5<f64> = Array::from_shape_vec(
+            (
+                intensity.len(),
+                threshold.len(),
+                slope.len(),
+                lower_asymptote.len(),
+                lapse_rate.len(),
+            ),
+            want_vec,
+        )
+        .unwrap();
+
+        let stim_domain = WeibullStimDomain::new(intensity);
+        let param_domain = WeibullParamDomain::new(threshold, slope, lower_asymptote, lapse_rate);
+        let prior_pdf = WeibullParamPDF::new(&param_domain, None, None, None, None).unwrap();
+
+        let weibull = Weibull::new(
+            stim_domain,
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mean,
+        )
+        .unwrap();
+
+        let result = weibull.likelihoods.index_axis(Axis(0), 0);
+        assert!(result.abs_diff_eq(&want.clone().into_dyn(), 1e-8));
+        let result = weibull.likelihoods.index_axis(Axis(0), 1);
+        assert!(result.abs_diff_eq(&want.mapv(|v| 1. - v).into_dyn(), 1e-8));
+    }
+
+    #[test]
+    fn test_weibull_session() {
+        let intensity: Array1<f64> = Array1::range(-3.5, -0.4, 0.25);
+        let threshold: Array1<f64> = Array1::range(-2.5, -0.9, 0.5);
+        let slope: Array1<f64> = arr1(&[2., 4.]);
+        let lower_asymptote: Array1<f64> = arr1(&[0.5]);
+        let lapse_rate: Array1<f64> = arr1(&[0.01, 0.05]);
+
+        let stim_domain = WeibullStimDomain::new(intensity);
+        let param_domain = WeibullParamDomain::new(threshold, slope, lower_asymptote, lapse_rate);
+        let prior_pdf = WeibullParamPDF::new(&param_domain, None, None, None, None).unwrap();
+
+        let mut weibull = Weibull::new(
+            stim_domain,
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mean,
+        )
+        .unwrap();
+
+        // want values are generated by Python code
+        assert_eq!(weibull.next_stim().unwrap()[0], -1.75);
+        assert!((weibull.entropy - 2.6307654200999773).abs() < 1e-10);
+
+        for (x, outcome) in [
+            (-1.75, Outcome::Correct),
+            (-2.5, Outcome::Incorrect),
+            (-2., Outcome::Correct),
+            (-2.25, Outcome::Incorrect),
+            (-1., Outcome::Correct),
+        ]
+        .iter()
+        {
+            weibull.update(*x, *outcome).unwrap();
+        }
+        assert_eq!(weibull.next_stim().unwrap()[0], -1.75);
+        assert!((weibull.entropy - 2.3322178479001607).abs() < 1e-10);
+
+        let estimates = weibull.get_param_estimates();
+        assert!((estimates.threshold - -1.6994860499625337).abs() < 1e-10);
+        assert!((estimates.slope - 3.0470445904407724).abs() < 1e-10);
+        assert!((estimates.lower_asymptote - 0.5).abs() < 1e-10);
+        assert!((estimates.lapse_rate - 0.029563080373365677).abs() < 1e-10);
+
+        weibull.param_estimation_method = ParamEstimationMethod::Mode;
+        assert_eq!(
+            weibull.get_param_estimates(),
+            WeibullParams {
+                threshold: -2.,
+                slope: 4.,
+                lower_asymptote: 0.5,
+                lapse_rate: 0.01,
+            }
+        );
+    }
+
+    #[test]
+    fn test_weibull_stim_scale() {
+        // want values are generated by Python code
+        let cases = [
+            (
+                StimScale::Linear,
+                arr1(&[0.01, 0.02, 0.05, 0.1]),
+                arr1(&[0.02, 0.04]),
+                [
+                    0.6083876162950116,
+                    0.5414513970287509,
+                    0.5296875992213969,
+                    0.5038132102524807,
+                    0.8097390738259933,
+                    0.8097390738259933,
+                    0.6083876162950116,
+                    0.5414513970287509,
+                    0.9890540774732484,
+                    0.9899999999908626,
+                    0.8872904202959621,
+                    0.9348120155364272,
+                    0.989999999993195,
+                    0.99,
+                    0.9890540774732484,
+                    0.9899999999908626,
+                ],
+            ),
+            (
+                StimScale::Decibel,
+                arr1(&[-40., -30., -20., -10.]),
+                arr1(&[-30., -25.]),
+                [
+                    0.5466296651623799,
+                    0.5086365505175053,
+                    0.5152527227733631,
+                    0.5011605964679247,
+                    0.8097390738259933,
+                    0.8097390738259933,
+                    0.632842227086088,
+                    0.561173139927883,
+                    0.9899777540344163,
+                    0.99,
+                    0.9692586823846295,
+                    0.9897287018022135,
+                    0.99,
+                    0.99,
+                    0.9899999999999909,
+                    0.99,
+                ],
+            ),
+        ];
+        for (stim_scale, intensity, threshold, want) in cases.iter() {
+            let stim_domain = WeibullStimDomain::new(intensity.clone());
+            let param_domain: ParamDomain = WeibullParamDomain::new(
+                threshold.clone(),
+                arr1(&[2., 3.5]),
+                arr1(&[0.5]),
+                arr1(&[0.01]),
+            )
+            .into();
+            let prior_pdf = ParamPDF::new(&param_domain, vec![None; 4]).unwrap();
+            let weibull = QuestPlus::with_pf(
+                WeibullFunction::new(*stim_scale),
+                stim_domain,
+                param_domain,
+                prior_pdf,
+                StimSelectionMethod::MinEntropy,
+                ParamEstimationMethod::Mean,
+            )
+            .unwrap();
+
+            let want = Array::from_shape_vec((4, 2, 2, 1, 1), want.to_vec()).unwrap();
+            let result = weibull.likelihoods.index_axis(Axis(0), 0);
+            assert!(result.abs_diff_eq(&want.into_dyn(), 1e-8));
+        }
+
+        for (name, intensity, threshold) in [
+            ("intensity", arr1(&[0., 1.]), arr1(&[1.])),
+            ("threshold", arr1(&[1., 2.]), arr1(&[-1., 1.])),
+        ] {
+            let param_domain: ParamDomain =
+                WeibullParamDomain::new(threshold, arr1(&[2.]), arr1(&[0.5]), arr1(&[0.01])).into();
+            let prior_pdf = ParamPDF::new(&param_domain, vec![None; 4]).unwrap();
+            let res = QuestPlus::<_, f64>::with_pf(
+                WeibullFunction::new(StimScale::Linear),
+                WeibullStimDomain::new(intensity),
+                param_domain,
+                prior_pdf,
+                StimSelectionMethod::MinEntropy,
+                ParamEstimationMethod::Mean,
+            );
+            match res {
+                Err(QuestPlusError::ParameterNotPositive(n, _)) => assert_eq!(n, name),
+                r => panic!("unexpected result: {:?}", r.map(|_| ())),
+            }
+        }
+    }
+}
@@ -0,0 +1,1522 @@
+use crate::domain::{check_finite, ParamDomain, ParamPDF, PriorPDFFactory, Stim, StimDomain};
+use crate::error::QuestPlusError;
+use crate::pf::PsychometricFunction;
+use crate::{
+    cast, Float, ParamEstimationMethod, PosteriorDomain, StimSelectionMethod, StopReason, StopRule,
+};
+use ndarray::prelude::*;
+use ndarray::Data;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use std::borrow::{Borrow, Cow};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// QUEST+ engine for any psychometric function.
+///
+/// `likelihoods` has a leading outcome axis followed by one axis per stimulus dimension and one
+/// axis per parameter, and `prior_pdf`/`posterior_pdf` have one axis per parameter in the order of
+/// `param_domain`. They are stored as `F`, while domains, estimates and entropies are `f64`.
+///
+/// With `PosteriorDomain::Log`, `likelihoods` and `posterior_pdf` hold natural logs instead of
+/// probabilities. Use `linear_posterior_pdf` to read the posterior in either domain.
+///
+/// With the `serde` feature the whole session can be saved and resumed. `likelihoods` are not
+/// written and are rebuilt from the psychometric function on load.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        try_from = "QuestPlusState<P, F>",
+        bound(
+            serialize = "P: serde::Serialize, P::Outcome: serde::Serialize, F: serde::Serialize",
+            deserialize = "P: serde::Deserialize<'de>, P::Outcome: serde::Deserialize<'de>, \
+                           F: serde::Deserialize<'de>"
+        )
+    )
+)]
+pub struct QuestPlus<P: PsychometricFunction, F: Float = f64> {
+    pub pf: P,
+    pub stim_domain: StimDomain,
+    pub param_domain: ParamDomain,
+    pub prior_pdf: ArrayD<F>,
+    pub posterior_pdf: ArrayD<F>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
+    pub likelihoods: ArrayD<F>,
+    /// Set with `set_posterior_domain`, which converts `likelihoods` and `posterior_pdf`.
+    pub posterior_domain: PosteriorDomain,
+    pub stim_selection_method: StimSelectionMethod,
+    pub param_estimation_method: ParamEstimationMethod,
+    pub resp_history: Vec<P::Outcome>,
+    pub stim_history: Vec<Stim>,
+    /// Values of `get_param_estimate_values` after every update.
+    pub estimate_history: Vec<Vec<f64>>,
+    /// Entropy of the posterior (in nats) after every update.
+    pub entropy_history: Vec<f64>,
+    pub entropy: f64,
+    pub rng: Pcg64,
+    /// Parameters whose posterior entropy drives stimulus selection; the others are marginalized
+    /// out as nuisance parameters. Empty means every parameter.
+    pub params_of_interest: Vec<String>,
+    /// The run is finished as soon as any of these rules fires.
+    pub stop_rules: Vec<StopRule>,
+    pub stop_reason: Option<StopReason>,
+}
+
+impl<P: PsychometricFunction, F: Float> QuestPlus<P, F> {
+    pub fn with_pf(
+        pf: P,
+        stim_domain: StimDomain,
+        param_domain: ParamDomain,
+        prior_pdf: ArrayD<F>,
+        stim_selection_method: StimSelectionMethod,
+        param_estimation_method: ParamEstimationMethod,
+    ) -> Result<Self, QuestPlusError> {
+        check_names(&pf.stim_names(), &stim_domain.names())?;
+        check_names(&pf.param_names(), &param_domain.names())?;
+        check_finite(&stim_domain.dims)?;
+        check_finite(&param_domain.params)?;
+        check_stim_selection_method(&stim_selection_method)?;
+        if prior_pdf.shape() != param_domain.shape().as_slice() {
+            return Err(QuestPlusError::PriorShapeNotMatch(
+                prior_pdf.shape().to_vec(),
+                param_domain.shape(),
+            ));
+        }
+        let likelihoods = Self::gen_likelihoods(&pf, &stim_domain, &param_domain)?;
+        let posterior_pdf = prior_pdf.clone();
+        let resp_history = Vec::new();
+        let stim_history = Vec::new();
+        let estimate_history = Vec::new();
+        let entropy_history = Vec::new();
+        let entropy = f64::MAX;
+        let rng = Pcg64::from_entropy();
+        let params_of_interest = Vec::new();
+        let stop_rules = Vec::new();
+        let stop_reason = None;
+        Ok(QuestPlus {
+            pf,
+            stim_domain,
+            param_domain,
+            prior_pdf,
+            posterior_pdf,
+            likelihoods,
+            posterior_domain: PosteriorDomain::Linear,
+            stim_selection_method,
+            param_estimation_method,
+            resp_history,
+            stim_history,
+            estimate_history,
+            entropy_history,
+            entropy,
+            rng,
+            params_of_interest,
+            stop_rules,
+            stop_reason,
+        })
+    }
+
+    /// Same as `with_pf`, with the domains and priors given as maps from name to values like
+    /// `param_domain=dict(...)` in Python questplus. Parameters left out of `priors` get a
+    /// uniform prior.
+    pub fn with_maps<K: Borrow<str> + Eq + Hash>(
+        pf: P,
+        stim_domain: HashMap<K, Array1<f64>>,
+        param_domain: HashMap<K, Array1<f64>>,
+        priors: HashMap<K, Array1<f64>>,
+        stim_selection_method: StimSelectionMethod,
+        param_estimation_method: ParamEstimationMethod,
+    ) -> Result<Self, QuestPlusError> {
+        let stim_domain = StimDomain::from_map(&pf.stim_names(), stim_domain)?;
+        let param_domain = ParamDomain::from_map(&pf.param_names(), param_domain)?;
+        let prior_pdf = ParamPDF::from_map(&param_domain, priors)?.mapv(cast);
+        Self::with_pf(
+            pf,
+            stim_domain,
+            param_domain,
+            prior_pdf,
+            stim_selection_method,
+            param_estimation_method,
+        )
+    }
+
+    /// Reseeds the random number generator used by `StimSelectionMethod::MinNEntropy`.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Pcg64::seed_from_u64(seed);
+    }
+
+    /// Updates the posterior with the response observed at `stim`.
+    ///
+    /// Fails with `ImpossibleOutcome`, leaving the session unchanged, if `outcome` has zero
+    /// likelihood wherever the posterior has mass.
+    pub fn update<S: Into<Stim>>(
+        &mut self,
+        stim: S,
+        outcome: P::Outcome,
+    ) -> Result<(), QuestPlusError> {
+        let stim = stim.into();
+        let stim_index = self.stim_domain.index_of(&stim)?;
+        let outcome_index = self.outcome_index(outcome)?;
+        let likelihood = self
+            .by_stim(&self.likelihoods)
+            .index_axis_move(Axis(0), outcome_index)
+            .index_axis_move(Axis(0), stim_index);
+        let posterior_pdf = match self.posterior_domain {
+            PosteriorDomain::Linear => {
+                let posterior_pdf = &self.posterior_pdf * &likelihood;
+                let sum = posterior_pdf.sum();
+                if sum.is_nan() || sum <= F::zero() {
+                    return Err(impossible_outcome(&stim, outcome));
+                }
+                posterior_pdf.mapv(|v| v / sum)
+            }
+            PosteriorDomain::Log => {
+                let log_posterior_pdf = &self.posterior_pdf + &likelihood;
+                let log_sum = log_sum_exp(&log_posterior_pdf);
+                if !log_sum.is_finite() {
+                    return Err(impossible_outcome(&stim, outcome));
+                }
+                log_posterior_pdf.mapv(|v| v - log_sum)
+            }
+        };
+        self.posterior_pdf = posterior_pdf;
+
+        self.stim_history.push(stim);
+        self.resp_history.push(outcome);
+        self.estimate_history.push(self.get_param_estimate_values());
+        self.entropy_history.push(self.posterior_entropy());
+        self.stop_reason = self.check_stop_rules(&self.stop_rules)?;
+        Ok(())
+    }
+
+    /// Switches the representation of the posterior, converting `likelihoods` and the current
+    /// posterior in place.
+    pub fn set_posterior_domain(&mut self, posterior_domain: PosteriorDomain) {
+        if posterior_domain == self.posterior_domain {
+            return;
+        }
+        let convert = match posterior_domain {
+            PosteriorDomain::Linear => F::exp,
+            PosteriorDomain::Log => F::ln,
+        };
+        self.likelihoods.mapv_inplace(convert);
+        self.posterior_pdf.mapv_inplace(convert);
+        self.posterior_domain = posterior_domain;
+    }
+
+    /// Posterior as probabilities, whatever `posterior_domain` it is stored in.
+    pub fn linear_posterior_pdf(&self) -> Cow<'_, ArrayD<F>> {
+        match self.posterior_domain {
+            PosteriorDomain::Linear => Cow::Borrowed(&self.posterior_pdf),
+            PosteriorDomain::Log => Cow::Owned(self.posterior_pdf.mapv(F::exp)),
+        }
+    }
+
+    /// Entropy of the posterior in nats.
+    fn posterior_entropy(&self) -> f64 {
+        match self.posterior_domain {
+            PosteriorDomain::Linear => entropy(&self.posterior_pdf),
+            PosteriorDomain::Log => log_entropy(&self.posterior_pdf),
+        }
+        .to_f64()
+        .expect("float converts into f64")
+    }
+
+    /// Restricts the entropy objective to the named parameters, as in the psi-marginal method.
+    pub fn set_params_of_interest(&mut self, names: &[&str]) -> Result<(), QuestPlusError> {
+        let param_names = self.param_domain.names();
+        let unknown: HashSet<String> = names
+            .iter()
+            .filter(|n| !param_names.contains(n))
+            .map(|n| n.to_string())
+            .collect();
+        if !unknown.is_empty() {
+            return Err(QuestPlusError::ParameterNotExists(
+                unknown,
+                param_names.iter().map(|n| n.to_string()).collect(),
+            ));
+        }
+        self.params_of_interest = names.iter().map(|n| n.to_string()).collect();
+        Ok(())
+    }
+
+    /// Replaces the stop rules and checks them against the current posterior.
+    pub fn set_stop_rules(&mut self, stop_rules: Vec<StopRule>) -> Result<(), QuestPlusError> {
+        self.stop_reason = self.check_stop_rules(&stop_rules)?;
+        self.stop_rules = stop_rules;
+        Ok(())
+    }
+
+    /// Whether any of `stop_rules` fired after the last update.
+    pub fn is_finished(&self) -> bool {
+        self.stop_reason.is_some()
+    }
+
+    /// Rebuilds the session from the prior by applying recorded `(stim, outcome)` trials in order.
+    ///
+    /// `entropy` is set to the expected entropy of the last recorded stimulus, as `next_stim`
+    /// would have done before the last trial of a live session. The session is left unchanged
+    /// if any trial is invalid.
+    pub fn replay<S, I>(&mut self, trials: I) -> Result<(), QuestPlusError>
+    where
+        S: Into<Stim>,
+        I: IntoIterator<Item = (S, P::Outcome)>,
+    {
+        let trials: Vec<(Stim, P::Outcome)> = trials
+            .into_iter()
+            .map(|(stim, outcome)| (stim.into(), outcome))
+            .collect();
+        for (stim, outcome) in &trials {
+            self.stim_domain.index_of(stim)?;
+            self.outcome_index(*outcome)?;
+        }
+
+        let prior_pdf = match self.posterior_domain {
+            PosteriorDomain::Linear => self.prior_pdf.clone(),
+            PosteriorDomain::Log => self.prior_pdf.mapv(F::ln),
+        };
+        let saved = (
+            std::mem::replace(&mut self.posterior_pdf, prior_pdf),
+            std::mem::take(&mut self.stim_history),
+            std::mem::take(&mut self.resp_history),
+            std::mem::take(&mut self.estimate_history),
+            std::mem::take(&mut self.entropy_history),
+            std::mem::replace(&mut self.entropy, f64::MAX),
+            self.stop_reason.take(),
+        );
+        let result = self.replay_trials(trials);
+        if result.is_err() {
+            self.posterior_pdf = saved.0;
+            self.stim_history = saved.1;
+            self.resp_history = saved.2;
+            self.estimate_history = saved.3;
+            self.entropy_history = saved.4;
+            self.entropy = saved.5;
+            self.stop_reason = saved.6;
+        }
+        result
+    }
+
+    fn replay_trials(&mut self, mut trials: Vec<(Stim, P::Outcome)>) -> Result<(), QuestPlusError> {
+        self.stop_reason = self.check_stop_rules(&self.stop_rules)?;
+        let last = trials.pop();
+        for (stim, outcome) in trials {
+            self.update(stim, outcome)?;
+        }
+        if let Some((stim, outcome)) = last {
+            self.entropy = self.expected_entropies()[self.stim_domain.index_of(&stim)?];
+            self.update(stim, outcome)?;
+        }
+        Ok(())
+    }
+
+    /// Selects the stimulus to present next according to `stim_selection_method`.
+    pub fn next_stim(&mut self) -> Result<Stim, QuestPlusError> {
+        check_stim_selection_method(&self.stim_selection_method)?;
+        let expected_entropies = self.expected_entropies();
+        let index = match self.stim_selection_method {
+            StimSelectionMethod::MinEntropy => {
+                let mut index = 0;
+                for (i, h) in expected_entropies.iter().enumerate() {
+                    if *h < expected_entropies[index] {
+                        index = i;
+                    }
+                }
+                index
+            }
+            StimSelectionMethod::MinNEntropy(n) => {
+                let mut indices: Vec<usize> = (0..expected_entropies.len()).collect();
+                indices.sort_by(|&a, &b| expected_entropies[a].total_cmp(&expected_entropies[b]));
+                let n = (n as usize).min(indices.len());
+                indices[self.rng.gen_range(0..n)]
+            }
+        };
+        self.entropy = expected_entropies[index];
+        Ok(self.stim_domain.point(index))
+    }
+
+    /// Estimates the parameters from the posterior according to `param_estimation_method`.
+    pub fn get_param_estimates(&self) -> P::Params {
+        self.pf.params(&self.get_param_estimate_values())
+    }
+
+    /// Same as `get_param_estimates`, as plain values in the order of `param_domain`.
+    pub fn get_param_estimate_values(&self) -> Vec<f64> {
+        match self.param_estimation_method {
+            ParamEstimationMethod::Mode => {
+                let mut mode = IxDyn(&vec![0; self.posterior_pdf.ndim()]);
+                let mut max = F::neg_infinity();
+                for (index, &p) in self.posterior_pdf.indexed_iter() {
+                    if p > max {
+                        max = p;
+                        mode = index;
+                    }
+                }
+                self.param_domain
+                    .params
+                    .iter()
+                    .enumerate()
+                    .map(|(axis, (_, values))| values[mode[axis]])
+                    .collect::<Vec<f64>>()
+            }
+            ParamEstimationMethod::Mean => {
+                let mut values = vec![0.; self.param_domain.params.len()];
+                for (index, &p) in self.linear_posterior_pdf().indexed_iter() {
+                    let p = p.to_f64().expect("float converts into f64");
+                    for (axis, (_, domain)) in self.param_domain.params.iter().enumerate() {
+                        values[axis] += p * domain[index[axis]];
+                    }
+                }
+                values
+            }
+        }
+    }
+
+    /// Marginal posterior of the parameter `name`.
+    pub fn marginal_posterior_pdf(&self, name: &str) -> Result<Array1<f64>, QuestPlusError> {
+        let names = self.param_domain.names();
+        let axis = match names.iter().position(|n| *n == name) {
+            Some(axis) => axis,
+            None => {
+                return Err(QuestPlusError::ParameterNotExists(
+                    Some(name.to_string()).into_iter().collect(),
+                    names.iter().map(|n| n.to_string()).collect(),
+                ))
+            }
+        };
+        let mut pdf = self.linear_posterior_pdf().into_owned();
+        for i in (0..names.len()).rev() {
+            if i != axis {
+                pdf = pdf.sum_axis(Axis(i));
+            }
+        }
+        match pdf.into_dimensionality::<Ix1>() {
+            Ok(a) => Ok(a.mapv(|p| p.to_f64().expect("float converts into f64"))),
+            Err(e) => Err(QuestPlusError::NDArrayError(e)),
+        }
+    }
+
+    /// Equal-tailed credible interval holding `mass` of the marginal posterior of the parameter
+    /// `name`.
+    pub fn credible_interval(&self, name: &str, mass: f64) -> Result<(f64, f64), QuestPlusError> {
+        if !(mass > 0. && mass < 1.) {
+            return Err(QuestPlusError::InvalidCredibleMass(mass));
+        }
+        let (values, pdf) = self.marginal(name)?;
+        let quantile = |q: f64| {
+            let mut cdf = 0.;
+            for (v, p) in values.iter().zip(pdf.iter()) {
+                cdf += p;
+                if cdf >= q {
+                    return *v;
+                }
+            }
+            values[values.len() - 1]
+        };
+        let tail = (1. - mass) / 2.;
+        Ok((quantile(tail), quantile(1. - tail)))
+    }
+
+    /// Highest-density interval of the parameter `name`: the range spanned by the fewest grid
+    /// values whose marginal posterior holds at least `mass`.
+    pub fn highest_density_interval(
+        &self,
+        name: &str,
+        mass: f64,
+    ) -> Result<(f64, f64), QuestPlusError> {
+        if !(mass > 0. && mass < 1.) {
+            return Err(QuestPlusError::InvalidCredibleMass(mass));
+        }
+        let (values, pdf) = self.marginal(name)?;
+        let mut indices: Vec<usize> = (0..pdf.len()).collect();
+        indices.sort_by(|&a, &b| pdf[b].total_cmp(&pdf[a]));
+        let mut lower = f64::MAX;
+        let mut upper = f64::MIN;
+        let mut cdf = 0.;
+        for i in indices {
+            lower = lower.min(values[i]);
+            upper = upper.max(values[i]);
+            cdf += pdf[i];
+            if cdf >= mass {
+                break;
+            }
+        }
+        Ok((lower, upper))
+    }
+
+    /// Standard deviation of the marginal posterior of the parameter `name`.
+    pub fn posterior_sd(&self, name: &str) -> Result<f64, QuestPlusError> {
+        let (values, pdf) = self.marginal(name)?;
+        let mean = (values * &pdf).sum();
+        let var = values
+            .iter()
+            .zip(pdf.iter())
+            .map(|(v, p)| p * (v - mean).powi(2))
+            .sum::<f64>();
+        Ok(var.sqrt())
+    }
+
+    /// Domain of the parameter `name` together with its marginal posterior.
+    fn marginal(&self, name: &str) -> Result<(&Array1<f64>, Array1<f64>), QuestPlusError> {
+        let pdf = self.marginal_posterior_pdf(name)?;
+        let values = self.param_domain.get(name).expect("parameter exists");
+        Ok((values, pdf))
+    }
+
+    fn check_stop_rules(&self, rules: &[StopRule]) -> Result<Option<StopReason>, QuestPlusError> {
+        let mut reason = None;
+        for rule in rules {
+            let fired = self.check_stop_rule(rule)?;
+            if reason.is_none() {
+                reason = fired;
+            }
+        }
+        Ok(reason)
+    }
+
+    fn check_stop_rule(&self, rule: &StopRule) -> Result<Option<StopReason>, QuestPlusError> {
+        let reason = match rule {
+            StopRule::MaxTrials(n) => {
+                let trials = self.stim_history.len();
+                if trials >= *n {
+                    Some(StopReason::MaxTrials(trials))
+                } else {
+                    None
+                }
+            }
+            StopRule::EntropyBelow(threshold) => {
+                let h = self.posterior_entropy();
+                if h < *threshold {
+                    Some(StopReason::EntropyBelow(h))
+                } else {
+                    None
+                }
+            }
+            StopRule::CredibleIntervalWidth { param, mass, width } => {
+                let (lower, upper) = self.credible_interval(param, *mass)?;
+                if upper - lower < *width {
+                    Some(StopReason::CredibleIntervalWidth(
+                        param.clone(),
+                        upper - lower,
+                    ))
+                } else {
+                    None
+                }
+            }
+            StopRule::StableEstimate { trials, tolerance } => {
+                let history = &self.estimate_history;
+                if history.len() <= *trials {
+                    None
+                } else {
+                    let window = &history[history.len() - trials - 1..];
+                    let stable = (0..self.param_domain.params.len()).all(|i| {
+                        let min = window.iter().map(|v| v[i]).fold(f64::MAX, f64::min);
+                        let max = window.iter().map(|v| v[i]).fold(f64::MIN, f64::max);
+                        max - min <= *tolerance
+                    });
+                    if stable {
+                        Some(StopReason::StableEstimate(*trials))
+                    } else {
+                        None
+                    }
+                }
+            }
+            StopRule::All(rules) => {
+                let mut reasons = Vec::with_capacity(rules.len());
+                for rule in rules {
+                    reasons.push(self.check_stop_rule(rule)?);
+                }
+                if reasons.iter().all(|r| r.is_some()) {
+                    Some(StopReason::All(reasons.into_iter().flatten().collect()))
+                } else {
+                    None
+                }
+            }
+        };
+        Ok(reason)
+    }
+
+    fn outcome_index(&self, outcome: P::Outcome) -> Result<usize, QuestPlusError> {
+        match self.pf.outcomes().iter().position(|&o| o == outcome) {
+            Some(i) => Ok(i),
+            None => Err(QuestPlusError::OutcomeNotExists(format!("{:?}", outcome))),
+        }
+    }
+
+    /// View of `likelihoods` (or `log_likelihoods`) with the stimulus dimensions flattened into a
+    /// single axis.
+    fn by_stim<'a>(&self, likelihoods: &'a ArrayD<F>) -> ArrayViewD<'a, F> {
+        let mut shape = vec![likelihoods.len_of(Axis(0)), self.stim_domain.len()];
+        shape.extend(self.param_domain.shape());
+        likelihoods
+            .view()
+            .into_shape(shape)
+            .expect("likelihoods are in standard layout")
+    }
+
+    /// Expected entropy of the posterior after presenting each stimulus, averaged over outcomes.
+    /// Parameters outside `params_of_interest` are marginalized out first.
+    fn expected_entropies(&self) -> Array1<f64> {
+        self.expected_entropies_with::<DefaultIndexMap>()
+    }
+
+    fn expected_entropies_with<M: IndexMap>(&self) -> Array1<f64> {
+        let nuisance_axes: Vec<usize> = if self.params_of_interest.is_empty() {
+            Vec::new()
+        } else {
+            self.param_domain
+                .names()
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| !self.params_of_interest.iter().any(|p| p == *n))
+                .map(|(axis, _)| axis)
+                .collect()
+        };
+        let likelihoods = self.by_stim(&self.likelihoods);
+        let posterior_pdf = &self.posterior_pdf;
+        if self.posterior_domain == PosteriorDomain::Log {
+            let (log_likelihoods, log_posterior_pdf) = (likelihoods, posterior_pdf);
+            return M::map_indices(self.stim_domain.len(), |i| {
+                log_likelihoods
+                    .outer_iter()
+                    .map(|log_likelihood| {
+                        let mut new_log_posterior_pdf =
+                            log_posterior_pdf + &log_likelihood.index_axis(Axis(0), i);
+                        for &axis in nuisance_axes.iter().rev() {
+                            new_log_posterior_pdf = new_log_posterior_pdf
+                                .map_axis(Axis(axis), |lane| log_sum_exp(&lane));
+                        }
+                        let log_pk = log_sum_exp(&new_log_posterior_pdf);
+                        if log_pk > F::neg_infinity() {
+                            log_pk.exp() * log_entropy(&new_log_posterior_pdf.mapv(|v| v - log_pk))
+                        } else {
+                            F::zero()
+                        }
+                    })
+                    .sum::<F>()
+                    .to_f64()
+                    .expect("float converts into f64")
+            })
+            .into();
+        }
+        M::map_indices(self.stim_domain.len(), |i| {
+            likelihoods
+                .outer_iter()
+                .map(|likelihood| {
+                    let mut new_posterior_pdf = posterior_pdf * &likelihood.index_axis(Axis(0), i);
+                    for &axis in nuisance_axes.iter().rev() {
+                        new_posterior_pdf = new_posterior_pdf.sum_axis(Axis(axis));
+                    }
+                    let pk = new_posterior_pdf.sum();
+                    if pk > F::zero() {
+                        pk * entropy(&new_posterior_pdf.mapv(|v| v / pk))
+                    } else {
+                        F::zero()
+                    }
+                })
+                .sum::<F>()
+                .to_f64()
+                .expect("float converts into f64")
+        })
+        .into()
+    }
+
+    /// Evaluates the psychometric function at every combination of stimulus and parameters.
+    fn gen_likelihoods(
+        pf: &P,
+        stim_domain: &StimDomain,
+        param_domain: &ParamDomain,
+    ) -> Result<ArrayD<F>, QuestPlusError> {
+        Self::gen_likelihoods_with::<DefaultIndexMap>(pf, stim_domain, param_domain)
+    }
+
+    fn gen_likelihoods_with<M: IndexMap>(
+        pf: &P,
+        stim_domain: &StimDomain,
+        param_domain: &ParamDomain,
+    ) -> Result<ArrayD<F>, QuestPlusError> {
+        let num_outcomes = pf.outcomes().len();
+        let stims = stim_domain.points();
+        let num_stims = stims.len();
+        let points = param_domain.points();
+        let num_points = points.len();
+        let rows = M::map_indices(num_stims, |i| -> Result<Vec<Vec<f64>>, QuestPlusError> {
+            let stim = &stims[i];
+            let mut row = Vec::with_capacity(num_points);
+            for params in points.iter() {
+                let probs = pf.eval(stim, params)?;
+                if probs.len() != num_outcomes {
+                    return Err(QuestPlusError::ParameterLengthNotMatch(
+                        "outcomes".to_string(),
+                        "probabilities".to_string(),
+                    ));
+                }
+                if probs.iter().any(|p| !(0. ..=1.).contains(p)) {
+                    return Err(QuestPlusError::OutcomeProbabilityOutOfRange(
+                        stim.to_vec(),
+                        params.clone(),
+                    ));
+                }
+                if (probs.iter().sum::<f64>() - 1.).abs() > 1e-9 {
+                    return Err(QuestPlusError::OutcomeProbabilityNotNormalized(
+                        stim.to_vec(),
+                        params.clone(),
+                    ));
+                }
+                row.push(probs);
+            }
+            Ok(row)
+        });
+        let mut v = vec![F::zero(); num_outcomes * num_stims * num_points];
+        for (i, row) in rows.into_iter().enumerate() {
+            for (j, probs) in row?.into_iter().enumerate() {
+                for (k, p) in probs.into_iter().enumerate() {
+                    v[(k * num_stims + i) * num_points + j] = cast(p);
+                }
+            }
+        }
+        let mut shape = vec![num_outcomes];
+        shape.extend(stim_domain.shape());
+        shape.extend(param_domain.shape());
+        match ArrayD::from_shape_vec(IxDyn(&shape), v) {
+            Ok(a) => Ok(a),
+            Err(e) => Err(QuestPlusError::NDArrayError(e)),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P, F> QuestPlus<P, F>
+where
+    P: PsychometricFunction + serde::Serialize + serde::de::DeserializeOwned,
+    P::Outcome: serde::Serialize + serde::de::DeserializeOwned,
+    F: Float + serde::Serialize + serde::de::DeserializeOwned,
+{
+    pub fn to_json(&self) -> Result<String, QuestPlusError> {
+        serde_json::to_string(self).map_err(QuestPlusError::JsonError)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, QuestPlusError> {
+        serde_json::from_str(s).map_err(QuestPlusError::JsonError)
+    }
+
+    pub fn to_bincode(&self) -> Result<Vec<u8>, QuestPlusError> {
+        bincode::serialize(self).map_err(QuestPlusError::BincodeError)
+    }
+
+    pub fn from_bincode(bytes: &[u8]) -> Result<Self, QuestPlusError> {
+        bincode::deserialize(bytes).map_err(QuestPlusError::BincodeError)
+    }
+}
+
+/// Saved form of `QuestPlus`, which omits `likelihoods`.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(bound(
+    deserialize = "P: serde::Deserialize<'de>, P::Outcome: serde::Deserialize<'de>, \
+                   F: serde::Deserialize<'de>"
+))]
+struct QuestPlusState<P: PsychometricFunction, F: Float> {
+    pf: P,
+    stim_domain: StimDomain,
+    param_domain: ParamDomain,
+    prior_pdf: ArrayD<F>,
+    posterior_pdf: ArrayD<F>,
+    posterior_domain: PosteriorDomain,
+    stim_selection_method: StimSelectionMethod,
+    param_estimation_method: ParamEstimationMethod,
+    resp_history: Vec<P::Outcome>,
+    stim_history: Vec<Stim>,
+    estimate_history: Vec<Vec<f64>>,
+    entropy_history: Vec<f64>,
+    entropy: f64,
+    rng: Pcg64,
+    params_of_interest: Vec<String>,
+    stop_rules: Vec<StopRule>,
+    stop_reason: Option<StopReason>,
+}
+
+#[cfg(feature = "serde")]
+impl<P: PsychometricFunction, F: Float> std::convert::TryFrom<QuestPlusState<P, F>>
+    for QuestPlus<P, F>
+{
+    type Error = QuestPlusError;
+
+    fn try_from(state: QuestPlusState<P, F>) -> Result<Self, QuestPlusError> {
+        if state.posterior_pdf.shape() != state.prior_pdf.shape() {
+            return Err(QuestPlusError::NDArrayError(
+                ndarray::ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape),
+            ));
+        }
+        let mut qp = QuestPlus::with_pf(
+            state.pf,
+            state.stim_domain,
+            state.param_domain,
+            state.prior_pdf,
+            state.stim_selection_method,
+            state.param_estimation_method,
+        )?;
+        qp.set_posterior_domain(state.posterior_domain);
+        qp.posterior_pdf = state.posterior_pdf;
+        qp.resp_history = state.resp_history;
+        qp.stim_history = state.stim_history;
+        qp.estimate_history = state.estimate_history;
+        qp.entropy_history = state.entropy_history;
+        qp.entropy = state.entropy;
+        qp.rng = state.rng;
+        qp.params_of_interest = state.params_of_interest;
+        qp.stop_rules = state.stop_rules;
+        qp.stop_reason = state.stop_reason;
+        Ok(qp)
+    }
+}
+
+fn impossible_outcome<O: Debug>(stim: &Stim, outcome: O) -> QuestPlusError {
+    QuestPlusError::ImpossibleOutcome(stim.to_vec(), format!("{:?}", outcome))
+}
+
+fn check_stim_selection_method(method: &StimSelectionMethod) -> Result<(), QuestPlusError> {
+    match method {
+        StimSelectionMethod::MinNEntropy(n) if *n < 1 => {
+            Err(QuestPlusError::InvalidMinNEntropyN(*n))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_names(want: &[&str], got: &[&str]) -> Result<(), QuestPlusError> {
+    if want == got {
+        return Ok(());
+    }
+    let want_set: HashSet<String> = want.iter().map(|s| s.to_string()).collect();
+    let got_set: HashSet<String> = got.iter().map(|s| s.to_string()).collect();
+    let unknown: HashSet<String> = got_set.difference(&want_set).cloned().collect();
+    if !unknown.is_empty() {
+        return Err(QuestPlusError::ParameterNotExists(unknown, want_set));
+    }
+    let missing: HashSet<String> = want_set.difference(&got_set).cloned().collect();
+    if !missing.is_empty() {
+        return Err(QuestPlusError::ParameterNotExists(missing, got_set));
+    }
+    Err(QuestPlusError::ParameterOrderNotMatch(
+        want.iter().map(|s| s.to_string()).collect(),
+        got.iter().map(|s| s.to_string()).collect(),
+    ))
+}
+
+/// Shannon entropy (in nats) of a probability mass function, treating `0 ln 0` as 0.
+fn entropy<F: Float, D: Dimension>(pdf: &Array<F, D>) -> F {
+    -pdf.iter()
+        .filter(|&&p| p > F::zero())
+        .map(|&p| p * p.ln())
+        .sum::<F>()
+}
+
+/// Strategy for evaluating a function at every index of `0..n`.
+trait IndexMap {
+    fn map_indices<T, G>(n: usize, f: G) -> Vec<T>
+    where
+        T: Send,
+        G: Fn(usize) -> T + Sync + Send;
+}
+
+/// Evaluates in order on the current thread.
+#[cfg(any(not(feature = "parallel"), test))]
+enum Serial {}
+
+#[cfg(any(not(feature = "parallel"), test))]
+impl IndexMap for Serial {
+    fn map_indices<T, G>(n: usize, f: G) -> Vec<T>
+    where
+        T: Send,
+        G: Fn(usize) -> T + Sync + Send,
+    {
+        (0..n).map(f).collect()
+    }
+}
+
+/// Evaluates across the threads of the global rayon pool.
+#[cfg(feature = "parallel")]
+enum Parallel {}
+
+#[cfg(feature = "parallel")]
+impl IndexMap for Parallel {
+    fn map_indices<T, G>(n: usize, f: G) -> Vec<T>
+    where
+        T: Send,
+        G: Fn(usize) -> T + Sync + Send,
+    {
+        use rayon::prelude::*;
+        (0..n).into_par_iter().map(f).collect()
+    }
+}
+
+#[cfg(feature = "parallel")]
+type DefaultIndexMap = Parallel;
+
+#[cfg(not(feature = "parallel"))]
+type DefaultIndexMap = Serial;
+
+/// `ln(sum(exp(log_pdf)))`, computed without underflow.
+fn log_sum_exp<F: Float, S: Data<Elem = F>, D: Dimension>(log_pdf: &ArrayBase<S, D>) -> F {
+    let max = log_pdf.fold(F::neg_infinity(), |m, &v| m.max(v));
+    if max == F::neg_infinity() {
+        return max;
+    }
+    max + log_pdf.iter().map(|&v| (v - max).exp()).sum::<F>().ln()
+}
+
+/// Shannon entropy (in nats) of a probability mass function given as natural logs.
+fn log_entropy<F: Float, D: Dimension>(log_pdf: &Array<F, D>) -> F {
+    -log_pdf
+        .iter()
+        .filter(|v| v.is_finite())
+        .map(|&v| v.exp() * v)
+        .sum::<F>()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{ParamDomain, ParamPDF, PriorPDFFactory, Stim, StimDomain};
+    use crate::error::QuestPlusError;
+    use crate::pf::{Outcome, PsychometricFunction};
+    use crate::qp::QuestPlus;
+    use crate::{
+        ParamEstimationMethod, PosteriorDomain, StimSelectionMethod, StopReason, StopRule,
+    };
+    use approx::AbsDiffEq;
+    use ndarray::prelude::*;
+    use std::collections::HashMap;
+
+    struct Step;
+
+    impl PsychometricFunction for Step {
+        type Params = Vec<f64>;
+        type Outcome = Outcome;
+
+        fn param_names(&self) -> Vec<&str> {
+            vec!["threshold", "lapse_rate"]
+        }
+
+        fn outcomes(&self) -> Vec<Outcome> {
+            vec![Outcome::Correct, Outcome::Incorrect]
+        }
+
+        fn eval(&self, stim: &[f64], params: &[f64]) -> Result<Vec<f64>, QuestPlusError> {
+            let p = if stim[0] >= params[0] {
+                1. - params[1]
+            } else {
+                params[1]
+            };
+            Ok(vec![p, 1. - p])
+        }
+
+        fn params(&self, values: &[f64]) -> Vec<f64> {
+            values.to_vec()
+        }
+    }
+
+    struct FrequencyStep;
+
+    impl PsychometricFunction for FrequencyStep {
+        type Params = Vec<f64>;
+        type Outcome = Outcome;
+
+        fn stim_names(&self) -> Vec<&str> {
+            vec!["contrast", "frequency"]
+        }
+
+        fn param_names(&self) -> Vec<&str> {
+            vec!["threshold", "slope"]
+        }
+
+        fn outcomes(&self) -> Vec<Outcome> {
+            vec![Outcome::Correct, Outcome::Incorrect]
+        }
+
+        fn eval(&self, stim: &[f64], params: &[f64]) -> Result<Vec<f64>, QuestPlusError> {
+            let p = if stim[0] >= params[0] + params[1] * stim[1] {
+                0.95
+            } else {
+                0.05
+            };
+            Ok(vec![p, 1. - p])
+        }
+
+        fn params(&self, values: &[f64]) -> Vec<f64> {
+            values.to_vec()
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Response {
+        Yes,
+        No,
+        Unsure,
+    }
+
+    struct YesNoUnsure {
+        normalized: bool,
+    }
+
+    impl PsychometricFunction for YesNoUnsure {
+        type Params = Vec<f64>;
+        type Outcome = Response;
+
+        fn param_names(&self) -> Vec<&str> {
+            vec!["threshold", "unsure_rate"]
+        }
+
+        fn outcomes(&self) -> Vec<Response> {
+            vec![Response::Yes, Response::No, Response::Unsure]
+        }
+
+        fn eval(&self, stim: &[f64], params: &[f64]) -> Result<Vec<f64>, QuestPlusError> {
+            let seen = if stim[0] >= params[0] { 0.9 } else { 0.1 };
+            let sure = if self.normalized { 1. - params[1] } else { 1. };
+            Ok(vec![sure * seen, sure * (1. - seen), params[1]])
+        }
+
+        fn params(&self, values: &[f64]) -> Vec<f64> {
+            values.to_vec()
+        }
+    }
+
+    fn step_param_domain() -> ParamDomain {
+        ParamDomain::new(vec![
+            ("threshold".to_string(), Array1::range(0., 10., 1.)),
+            ("lapse_rate".to_string(), arr1(&[0.05, 0.1])),
+        ])
+    }
+
+    #[test]
+    fn test_custom_pf() {
+        let stim_domain = StimDomain::new(Array1::range(0., 10., 1.));
+        let param_domain = step_param_domain();
+        let prior_pdf = ParamPDF::new(&param_domain, vec![None, None]).unwrap();
+        assert_eq!(prior_pdf.shape(), &[10, 2]);
+
+        let mut qp = QuestPlus::with_pf(
+            Step,
+            stim_domain,
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        )
+        .unwrap();
+        assert_eq!(qp.likelihoods.shape(), &[2, 10, 10, 2]);
+
+        for _ in 0..8 {
+            let x = qp.next_stim().unwrap();
+            let outcome = if x[0] >= 6. {
+                Outcome::Correct
+            } else {
+                Outcome::Incorrect
+            };
+            qp.update(x, outcome).unwrap();
+        }
+        assert_eq!(qp.get_param_estimates()[0], 6.);
+    }
+
+    #[test]
+    fn test_stop_rules() {
+        let param_domain = step_param_domain();
+        let prior_pdf = ParamPDF::new(&param_domain, vec![None, None]).unwrap();
+        let mut qp = QuestPlus::with_pf(
+            Step,
+            StimDomain::new(Array1::range(0., 10., 1.)),
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        )
+        .unwrap();
+
+        let res = qp.set_stop_rules(vec![StopRule::CredibleIntervalWidth {
+            param: "slope".to_string(),
+            mass: 0.9,
+            width: 1.,
+        }]);
+        assert!(matches!(res, Err(QuestPlusError::ParameterNotExists(_, _))));
+        let res = qp.set_stop_rules(vec![StopRule::CredibleIntervalWidth {
+            param: "threshold".to_string(),
+            mass: 1.5,
+            width: 1.,
+        }]);
+        assert!(matches!(res, Err(QuestPlusError::InvalidCredibleMass(_))));
+        assert!(qp.stop_rules.is_empty());
+
+        qp.set_stop_rules(vec![
+            StopRule::MaxTrials(30),
+            StopRule::CredibleIntervalWidth {
+                param: "threshold".to_string(),
+                mass: 0.9,
+                width: 1.5,
+            },
+        ])
+        .unwrap();
+        let mut trials = 0;
+        while !qp.is_finished() {
+            let x = qp.next_stim().unwrap();
+            let outcome = if x[0] >= 6. {
+                Outcome::Correct
+            } else {
+                Outcome::Incorrect
+            };
+            qp.update(x, outcome).unwrap();
+            trials += 1;
+        }
+        assert!(trials < 30);
+        match &qp.stop_reason {
+            Some(StopReason::CredibleIntervalWidth(param, width)) => {
+                assert_eq!(param, "threshold");
+                assert!(*width < 1.5);
+            }
+            r => panic!("unexpected stop reason: {:?}", r),
+        }
+
+        qp.set_stop_rules(vec![StopRule::StableEstimate {
+            trials: trials - 1,
+            tolerance: 0.,
+        }])
+        .unwrap();
+        assert!(!qp.is_finished());
+        qp.set_stop_rules(vec![StopRule::StableEstimate {
+            trials: 1,
+            tolerance: 0.,
+        }])
+        .unwrap();
+        assert_eq!(qp.stop_reason, Some(StopReason::StableEstimate(1)));
+
+        qp.set_stop_rules(vec![StopRule::All(vec![
+            StopRule::MaxTrials(3),
+            StopRule::EntropyBelow(0.),
+        ])])
+        .unwrap();
+        assert!(!qp.is_finished());
+    }
+
+    #[test]
+    fn test_log_posterior_domain() {
+        let new_qp = |posterior_domain| {
+            let param_domain = step_param_domain();
+            let prior_pdf = ParamPDF::new(&param_domain, vec![None, None]).unwrap();
+            let mut qp = QuestPlus::with_pf(
+                Step,
+                StimDomain::new(Array1::range(0., 10., 1.)),
+                param_domain,
+                prior_pdf,
+                StimSelectionMethod::MinEntropy,
+                ParamEstimationMethod::Mode,
+            )
+            .unwrap();
+            qp.set_posterior_domain(posterior_domain);
+            qp
+        };
+
+        let mut linear = new_qp(PosteriorDomain::Linear);
+        let mut log = new_qp(PosteriorDomain::Log);
+        for _ in 0..5 {
+            let stim = linear.next_stim().unwrap();
+            assert_eq!(log.next_stim().unwrap(), stim);
+            assert!((log.entropy - linear.entropy).abs() < 1e-10);
+            let outcome = if stim[0] >= 4. {
+                Outcome::Correct
+            } else {
+                Outcome::Incorrect
+            };
+            linear.update(stim.clone(), outcome).unwrap();
+            log.update(stim, outcome).unwrap();
+            assert!(log
+                .linear_posterior_pdf()
+                .abs_diff_eq(&linear.posterior_pdf, 1e-12));
+        }
+
+        // a threshold of 0 is driven below the smallest f64 in the linear domain and never
+        // recovers, while the log domain keeps track of it
+        let mut trials = vec![(0., Outcome::Incorrect); 400];
+        trials.extend(vec![(0., Outcome::Correct); 600]);
+        linear.replay(trials.clone()).unwrap();
+        log.replay(trials).unwrap();
+        assert_ne!(linear.get_param_estimates()[0], 0.);
+        assert_eq!(log.get_param_estimates()[0], 0.);
+        assert!(log.posterior_pdf.iter().all(|v| v.is_finite()));
+
+        log.set_posterior_domain(PosteriorDomain::Linear);
+        assert_eq!(log.posterior_domain, PosteriorDomain::Linear);
+        assert!(log.likelihoods.abs_diff_eq(&linear.likelihoods, 1e-12));
+    }
+
+    #[test]
+    fn test_impossible_outcome() {
+        for posterior_domain in [PosteriorDomain::Linear, PosteriorDomain::Log] {
+            let param_domain = ParamDomain::new(vec![
+                ("threshold".to_string(), Array1::range(0., 10., 1.)),
+                ("lapse_rate".to_string(), arr1(&[0.])),
+            ]);
+            let prior_pdf = ParamPDF::new(&param_domain, vec![None, None]).unwrap();
+            let mut qp = QuestPlus::with_pf(
+                Step,
+                StimDomain::new(Array1::range(0., 10., 1.)),
+                param_domain,
+                prior_pdf,
+                StimSelectionMethod::MinEntropy,
+                ParamEstimationMethod::Mode,
+            )
+            .unwrap();
+            qp.set_posterior_domain(posterior_domain);
+            let posterior_pdf = qp.posterior_pdf.clone();
+
+            match qp.update(9., Outcome::Incorrect) {
+                Err(QuestPlusError::ImpossibleOutcome(stim, outcome)) => {
+                    assert_eq!(stim, vec![9.]);
+                    assert_eq!(outcome, "Incorrect");
+                }
+                r => panic!("unexpected result: {:?}", r),
+            }
+            assert_eq!(qp.posterior_pdf, posterior_pdf);
+            assert!(qp.stim_history.is_empty());
+            assert!(qp.resp_history.is_empty());
+            assert!(qp.estimate_history.is_empty());
+            assert!(qp.entropy_history.is_empty());
+
+            qp.update(5., Outcome::Correct).unwrap();
+            let posterior_pdf = qp.posterior_pdf.clone();
+            let entropy = qp.entropy;
+            let res = qp.replay(vec![(5., Outcome::Correct), (9., Outcome::Incorrect)]);
+            assert!(matches!(res, Err(QuestPlusError::ImpossibleOutcome(_, _))));
+            assert_eq!(qp.posterior_pdf, posterior_pdf);
+            assert_eq!(qp.stim_history, vec![Stim::from(5.)]);
+            assert_eq!(qp.resp_history, vec![Outcome::Correct]);
+            assert_eq!(qp.estimate_history.len(), 1);
+            assert_eq!(qp.entropy_history.len(), 1);
+            assert_eq!(qp.entropy, entropy);
+        }
+    }
+
+    #[test]
+    fn test_mode_of_nan_posterior() {
+        let param_domain = step_param_domain();
+        let prior_pdf = ParamPDF::new(&param_domain, vec![None, None]).unwrap();
+        let mut qp = QuestPlus::with_pf(
+            Step,
+            StimDomain::new(Array1::range(0., 10., 1.)),
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        )
+        .unwrap();
+        qp.posterior_pdf.fill(f64::NAN);
+        assert_eq!(qp.get_param_estimate_values(), vec![0., 0.05]);
+    }
+
+    #[test]
+    fn test_param_domain_not_match() {
+        let param_domain = ParamDomain::new(vec![
+            ("threshold".to_string(), Array1::range(0., 10., 1.)),
+            ("slope".to_string(), arr1(&[1., 2.])),
+        ]);
+        let prior_pdf = ParamPDF::new(&param_domain, vec![None, None]).unwrap();
+        let res = QuestPlus::with_pf(
+            Step,
+            StimDomain::new(Array1::range(0., 10., 1.)),
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        );
+        match res {
+            Err(QuestPlusError::ParameterNotExists(unknown, _)) => {
+                assert!(unknown.contains("slope"))
+            }
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+
+        let res = QuestPlus::with_pf(
+            Step,
+            StimDomain::new(Array1::range(0., 10., 1.)),
+            step_param_domain(),
+            ParamPDF::ones(IxDyn(&[10, 3])),
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        );
+        match res {
+            Err(QuestPlusError::PriorShapeNotMatch(prior, domain)) => {
+                assert_eq!(prior, vec![10, 3]);
+                assert_eq!(domain, vec![10, 2]);
+            }
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_invalid_domain() {
+        let param_domain = step_param_domain();
+        let prior_pdf = ParamPDF::new(&param_domain, vec![None, None]).unwrap();
+        let res = QuestPlus::with_pf(
+            Step,
+            StimDomain::new(arr1(&[])),
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        );
+        match res {
+            Err(QuestPlusError::EmptyDomain(name)) => assert_eq!(name, "intensity"),
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+
+        let param_domain = ParamDomain::new(vec![
+            ("threshold".to_string(), arr1(&[0., f64::INFINITY])),
+            ("lapse_rate".to_string(), arr1(&[0.05])),
+        ]);
+        let prior_pdf = ParamPDF::new(&param_domain, vec![None, None]).unwrap();
+        let res = QuestPlus::with_pf(
+            Step,
+            StimDomain::new(Array1::range(0., 10., 1.)),
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        );
+        match res {
+            Err(QuestPlusError::DomainNotFinite(name)) => assert_eq!(name, "threshold"),
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_with_maps() {
+        let stim_domain = || {
+            let mut m = HashMap::new();
+            m.insert("intensity", Array1::range(0., 10., 1.));
+            m
+        };
+        let mut param_domain = HashMap::new();
+        param_domain.insert("lapse_rate", arr1(&[0.05, 0.1]));
+        param_domain.insert("threshold", Array1::range(0., 10., 1.));
+        let mut priors = HashMap::new();
+        priors.insert("lapse_rate", arr1(&[3., 1.]));
+
+        let qp: QuestPlus<Step> = QuestPlus::with_maps(
+            Step,
+            stim_domain(),
+            param_domain.clone(),
+            priors,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        )
+        .unwrap();
+        assert_eq!(qp.param_domain.names(), vec!["threshold", "lapse_rate"]);
+        let expected = ParamPDF::new(&step_param_domain(), vec![None, Some(arr1(&[3., 1.]))]);
+        assert_eq!(qp.prior_pdf, expected.unwrap());
+
+        let mut priors = HashMap::new();
+        priors.insert("slope", arr1(&[1., 2.]));
+        let res: Result<QuestPlus<Step>, _> = QuestPlus::with_maps(
+            Step,
+            stim_domain(),
+            param_domain.clone(),
+            priors,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        );
+        match res {
+            Err(QuestPlusError::ParameterNotExists(unknown, _)) => {
+                assert!(unknown.contains("slope"))
+            }
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+
+        param_domain.remove("lapse_rate");
+        let res: Result<QuestPlus<Step>, _> = QuestPlus::with_maps(
+            Step,
+            stim_domain(),
+            param_domain,
+            HashMap::new(),
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        );
+        match res {
+            Err(QuestPlusError::ParameterNotExists(missing, _)) => {
+                assert!(missing.contains("lapse_rate"))
+            }
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_multi_dimensional_stim() {
+        let stim_domain = StimDomain::with_dims(vec![
+            ("contrast".to_string(), Array1::range(0., 10., 1.)),
+            ("frequency".to_string(), arr1(&[0., 1., 2.])),
+        ]);
+        let param_domain = ParamDomain::new(vec![
+            ("threshold".to_string(), Array1::range(0., 6., 1.)),
+            ("slope".to_string(), arr1(&[0., 1., 2.])),
+        ]);
+        let prior_pdf = ParamPDF::new(&param_domain, vec![None, None]).unwrap();
+        let mut qp = QuestPlus::with_pf(
+            FrequencyStep,
+            stim_domain,
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        )
+        .unwrap();
+        assert_eq!(qp.likelihoods.shape(), &[2, 10, 3, 6, 3]);
+        assert_eq!(qp.stim_domain.point(13), Stim::from([4., 1.]));
+
+        for _ in 0..20 {
+            let stim = qp.next_stim().unwrap();
+            assert_eq!(stim.len(), 2);
+            let outcome = if stim[0] >= 2. + 1. * stim[1] {
+                Outcome::Correct
+            } else {
+                Outcome::Incorrect
+            };
+            qp.update(stim, outcome).unwrap();
+        }
+        assert_eq!(qp.stim_history.len(), 20);
+        assert_eq!(qp.get_param_estimates(), vec![2., 1.]);
+
+        for stim in [Stim::from([4., 0.5]), Stim::from(4.)].iter() {
+            match qp.update(stim.clone(), Outcome::Correct) {
+                Err(QuestPlusError::StimNotExists(x)) => assert_eq!(&x, &stim.0),
+                r => panic!("unexpected result: {:?}", r),
+            }
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_matches_serial() {
+        use crate::qp::{Parallel, Serial};
+
+        let stim_domain = StimDomain::with_dims(vec![
+            ("contrast".to_string(), Array1::range(0., 10., 1.)),
+            ("frequency".to_string(), arr1(&[0., 1., 2.])),
+        ]);
+        let param_domain = ParamDomain::new(vec![
+            ("threshold".to_string(), Array1::range(0., 6., 1.)),
+            ("slope".to_string(), arr1(&[0., 1., 2.])),
+        ]);
+        let serial = QuestPlus::<FrequencyStep>::gen_likelihoods_with::<Serial>(
+            &FrequencyStep,
+            &stim_domain,
+            &param_domain,
+        )
+        .unwrap();
+        let parallel = QuestPlus::<FrequencyStep>::gen_likelihoods_with::<Parallel>(
+            &FrequencyStep,
+            &stim_domain,
+            &param_domain,
+        )
+        .unwrap();
+        assert_eq!(serial, parallel);
+
+        let prior_pdf = ParamPDF::new(&param_domain, vec![None, None]).unwrap();
+        let mut qp = QuestPlus::with_pf(
+            FrequencyStep,
+            stim_domain,
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        )
+        .unwrap();
+        assert_eq!(qp.likelihoods, parallel);
+        qp.update([4., 1.], Outcome::Correct).unwrap();
+        qp.update([2., 2.], Outcome::Incorrect).unwrap();
+        for posterior_domain in [PosteriorDomain::Linear, PosteriorDomain::Log] {
+            qp.set_posterior_domain(posterior_domain);
+            for params_of_interest in [&[][..], &["threshold"][..]] {
+                qp.set_params_of_interest(params_of_interest).unwrap();
+                assert_eq!(
+                    qp.expected_entropies_with::<Serial>(),
+                    qp.expected_entropies_with::<Parallel>()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_more_than_two_outcomes() {
+        let param_domain = ParamDomain::new(vec![
+            ("threshold".to_string(), Array1::range(0., 10., 1.)),
+            ("unsure_rate".to_string(), arr1(&[0.1, 0.3])),
+        ]);
+        let prior_pdf = ParamPDF::new(&param_domain, vec![None, None]).unwrap();
+        let mut qp = QuestPlus::with_pf(
+            YesNoUnsure { normalized: true },
+            StimDomain::new(Array1::range(0., 10., 1.)),
+            param_domain.clone(),
+            prior_pdf.clone(),
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        )
+        .unwrap();
+        assert_eq!(qp.likelihoods.shape(), &[3, 10, 10, 2]);
+        let sum = qp.likelihoods.sum_axis(Axis(0));
+        assert!(sum.iter().all(|v| (v - 1.).abs() < 1e-12));
+
+        let responses = [Response::Unsure, Response::Yes, Response::No];
+        for i in 0..12 {
+            let stim = qp.next_stim().unwrap();
+            let response = if i % 4 == 3 {
+                responses[0]
+            } else if stim[0] >= 3. {
+                responses[1]
+            } else {
+                responses[2]
+            };
+            qp.update(stim, response).unwrap();
+        }
+        assert_eq!(qp.resp_history.len(), 12);
+        assert_eq!(qp.resp_history[3], Response::Unsure);
+        let estimates = qp.get_param_estimates();
+        assert_eq!(estimates, vec![3., 0.3]);
+
+        let res = QuestPlus::with_pf(
+            YesNoUnsure { normalized: false },
+            StimDomain::new(Array1::range(0., 10., 1.)),
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        );
+        assert!(matches!(
+            res,
+            Err(QuestPlusError::OutcomeProbabilityNotNormalized(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_outcome_probability_out_of_range() {
+        let param_domain = ParamDomain::new(vec![
+            ("threshold".to_string(), Array1::range(0., 10., 1.)),
+            ("lapse_rate".to_string(), arr1(&[0.05, 1.5])),
+        ]);
+        let prior_pdf = ParamPDF::new(&param_domain, vec![None, None]).unwrap();
+        let res = QuestPlus::<_, f64>::with_pf(
+            Step,
+            StimDomain::new(Array1::range(0., 10., 1.)),
+            param_domain,
+            prior_pdf,
+            StimSelectionMethod::MinEntropy,
+            ParamEstimationMethod::Mode,
+        );
+        match res {
+            Err(QuestPlusError::OutcomeProbabilityOutOfRange(stim, params)) => {
+                assert_eq!(stim, vec![0.]);
+                assert_eq!(params, vec![0., 1.5]);
+            }
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+    }
+}
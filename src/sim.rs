@@ -0,0 +1,168 @@
+use crate::domain::Stim;
+use crate::error::QuestPlusError;
+use crate::pf::PsychometricFunction;
+use crate::qp::QuestPlus;
+use crate::Float;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+
+/// Observer that responds according to a psychometric function with known parameters.
+#[derive(Debug)]
+pub struct SimulatedObserver<P: PsychometricFunction> {
+    pub pf: P,
+    pub params: Vec<f64>,
+}
+
+impl<P: PsychometricFunction> SimulatedObserver<P> {
+    /// `params` are the true parameter values in the order of `pf.param_names()`.
+    pub fn new(pf: P, params: Vec<f64>) -> Result<Self, QuestPlusError> {
+        if pf.param_names().len() != params.len() {
+            return Err(QuestPlusError::ParameterLengthNotMatch(
+                "param_names".to_string(),
+                "params".to_string(),
+            ));
+        }
+        Ok(SimulatedObserver { pf, params })
+    }
+
+    /// Draws a response to `stim` from the outcome probabilities of the psychometric function.
+    pub fn respond<R: Rng>(&self, stim: &Stim, rng: &mut R) -> Result<P::Outcome, QuestPlusError> {
+        let outcomes = self.pf.outcomes();
+        let probs = self.pf.eval(stim, &self.params)?;
+        let u: f64 = rng.gen();
+        let mut cum = 0.;
+        for (outcome, p) in outcomes.iter().zip(probs) {
+            cum += p;
+            if u < cum {
+                return Ok(*outcome);
+            }
+        }
+        Ok(outcomes[outcomes.len() - 1])
+    }
+}
+
+/// Record of one simulated session.
+#[derive(Debug)]
+pub struct SimulatedSession<P: PsychometricFunction> {
+    pub stims: Vec<Stim>,
+    pub outcomes: Vec<P::Outcome>,
+    /// Parameter estimates after every trial, in the order of `param_domain`.
+    pub estimates: Vec<Vec<f64>>,
+    /// Expected entropy of the stimulus chosen on every trial.
+    pub entropies: Vec<f64>,
+    /// Final estimates minus the true parameters of the observer.
+    pub errors: Vec<f64>,
+}
+
+/// Runs `n_sessions` sessions of `n_trials` trials each, with a fresh engine from `new_qp` per
+/// session. Results are deterministic for a given `seed`.
+pub fn simulate<P, F, G>(
+    new_qp: G,
+    observer: &SimulatedObserver<P>,
+    n_sessions: usize,
+    n_trials: usize,
+    seed: u64,
+) -> Result<Vec<SimulatedSession<P>>, QuestPlusError>
+where
+    P: PsychometricFunction,
+    F: Float,
+    G: Fn() -> Result<QuestPlus<P, F>, QuestPlusError>,
+{
+    let mut rng = Pcg64::seed_from_u64(seed);
+    let mut sessions = Vec::with_capacity(n_sessions);
+    for _ in 0..n_sessions {
+        let mut qp = new_qp()?;
+        if qp.param_domain.params.len() != observer.params.len() {
+            return Err(QuestPlusError::ParameterLengthNotMatch(
+                "param_domain".to_string(),
+                "params".to_string(),
+            ));
+        }
+        qp.seed(rng.gen());
+        let mut session = SimulatedSession {
+            stims: Vec::with_capacity(n_trials),
+            outcomes: Vec::with_capacity(n_trials),
+            estimates: Vec::with_capacity(n_trials),
+            entropies: Vec::with_capacity(n_trials),
+            errors: Vec::new(),
+        };
+        for _ in 0..n_trials {
+            let stim = qp.next_stim()?;
+            let outcome = observer.respond(&stim, &mut rng)?;
+            qp.update(stim.clone(), outcome)?;
+            session.stims.push(stim);
+            session.outcomes.push(outcome);
+            session.estimates.push(qp.get_param_estimate_values());
+            session.entropies.push(qp.entropy);
+        }
+        let last = match session.estimates.last() {
+            Some(values) => values.clone(),
+            None => qp.get_param_estimate_values(),
+        };
+        session.errors = last
+            .iter()
+            .zip(observer.params.iter())
+            .map(|(estimate, truth)| estimate - truth)
+            .collect();
+        sessions.push(session);
+    }
+    Ok(sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::QuestPlusError;
+    use crate::pf::{NormCDF, NormCDFFunction};
+    use crate::sim::{simulate, SimulatedObserver};
+    use crate::testing;
+    use crate::StimSelectionMethod;
+    use ndarray::prelude::*;
+
+    /// Shared fixture on a finer `mean` grid with `sd` fixed at the observer's value.
+    fn norm_cdf_fixed_sd() -> Result<NormCDF, QuestPlusError> {
+        let (stim_domain, mut param_domain) = testing::norm_cdf_domains();
+        param_domain.mean = Array1::range(5., 26., 1.);
+        param_domain.sd = arr1(&[2.]);
+        testing::norm_cdf_from(
+            stim_domain,
+            param_domain,
+            StimSelectionMethod::MinNEntropy(3),
+        )
+    }
+
+    #[test]
+    fn test_simulate() {
+        let observer =
+            SimulatedObserver::new(NormCDFFunction::default(), vec![15., 2., 0.5, 0.01]).unwrap();
+        let sessions = simulate(norm_cdf_fixed_sd, &observer, 4, 48, 42).unwrap();
+        assert_eq!(sessions.len(), 4);
+        for session in sessions.iter() {
+            assert_eq!(session.stims.len(), 48);
+            assert_eq!(session.estimates.len(), 48);
+            assert_eq!(session.entropies.len(), 48);
+            assert!(session.entropies[47] < session.entropies[0]);
+            assert!(session.errors[0].abs() < 3.);
+            assert!(session.errors[1].abs() < 1e-12);
+        }
+
+        let replayed = simulate(norm_cdf_fixed_sd, &observer, 4, 48, 42).unwrap();
+        for (a, b) in sessions.iter().zip(replayed.iter()) {
+            assert_eq!(a.stims, b.stims);
+            assert_eq!(a.outcomes, b.outcomes);
+            assert_eq!(a.estimates, b.estimates);
+        }
+        let other = simulate(norm_cdf_fixed_sd, &observer, 4, 48, 43).unwrap();
+        assert!(sessions
+            .iter()
+            .zip(other.iter())
+            .any(|(a, b)| a.outcomes != b.outcomes));
+    }
+
+    #[test]
+    fn test_observer_params_not_match() {
+        match SimulatedObserver::new(NormCDFFunction::default(), vec![15., 2.]) {
+            Err(QuestPlusError::ParameterLengthNotMatch(_, _)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
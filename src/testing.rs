@@ -0,0 +1,42 @@
+//! Fixtures shared by the unit tests.
+use crate::error::QuestPlusError;
+use crate::pf::{
+    NormCDF, NormCDFParamDomain, NormCDFParamPDF, NormCDFPriorPDFFactory, NormCDFStimDomain,
+};
+use crate::{ParamEstimationMethod, StimSelectionMethod};
+use ndarray::prelude::*;
+
+/// Domains of the normal CDF session whose reference values were generated by Python code.
+pub(crate) fn norm_cdf_domains() -> (NormCDFStimDomain, NormCDFParamDomain) {
+    let intensity: Array1<f64> = Array1::range(0., 30., 1.);
+    let mean: Array1<f64> = Array1::range(5., 25., 5.);
+    let sd: Array1<f64> = arr1(&[2., 4.]);
+    let lower_asymptote: Array1<f64> = arr1(&[0.5]);
+    let lapse_rate: Array1<f64> = arr1(&[0.01]);
+    (
+        NormCDFStimDomain::new(intensity),
+        NormCDFParamDomain::new(mean, sd, lower_asymptote, lapse_rate),
+    )
+}
+
+/// `NormCDF` over the given domains with a uniform prior, estimating by `Mean`.
+pub(crate) fn norm_cdf_from(
+    stim_domain: NormCDFStimDomain,
+    param_domain: NormCDFParamDomain,
+    stim_selection_method: StimSelectionMethod,
+) -> Result<NormCDF, QuestPlusError> {
+    let prior_pdf = NormCDFParamPDF::new(&param_domain, None, None, None, None)?;
+    NormCDF::new(
+        stim_domain,
+        param_domain,
+        prior_pdf,
+        stim_selection_method,
+        ParamEstimationMethod::Mean,
+    )
+}
+
+/// `NormCDF` over `norm_cdf_domains` with a uniform prior, estimating by `Mean`.
+pub(crate) fn norm_cdf(stim_selection_method: StimSelectionMethod) -> NormCDF {
+    let (stim_domain, param_domain) = norm_cdf_domains();
+    norm_cdf_from(stim_domain, param_domain, stim_selection_method).unwrap()
+}